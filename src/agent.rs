@@ -0,0 +1,269 @@
+use crate::{car::Car, controls::Controls, road::Road};
+
+/// Intelligent Driver Model: accelerates towards the desired speed and
+/// brakes to keep a safe, speed-dependent gap to the car ahead. Distances are
+/// in pixels and times in ticks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Idm {
+    /// Time to the car ahead the driver keeps at any speed.
+    pub time_headway: f64,
+    /// Bumper to bumper gap kept when standing still.
+    pub min_gap: f64,
+    pub max_acceleration: f64,
+    pub comfortable_deceleration: f64,
+    /// How sharply acceleration falls off near the desired speed.
+    pub exponent: f64,
+}
+
+impl Default for Idm {
+    fn default() -> Self {
+        Self {
+            time_headway: 30.0,
+            min_gap: 20.0,
+            max_acceleration: 0.1,
+            comfortable_deceleration: 0.15,
+            exponent: 4.0,
+        }
+    }
+}
+
+impl Idm {
+    /// Acceleration of a car at `speed`, given the bumper to bumper gap and
+    /// the speed of the car ahead, if there is one.
+    pub fn acceleration(&self, speed: f64, desired_speed: f64, leader: Option<(f64, f64)>) -> f64 {
+        let free_road = 1.0 - (speed / desired_speed.max(f64::EPSILON)).powf(self.exponent);
+
+        let interaction = match leader {
+            Some((gap, leader_speed)) => {
+                let desired_gap = self.min_gap
+                    + (speed * self.time_headway
+                        + speed * (speed - leader_speed)
+                            / (2.0
+                                * (self.max_acceleration * self.comfortable_deceleration).sqrt()))
+                    .max(0.0);
+                (desired_gap / gap.max(1.0)).powi(2)
+            }
+            None => 0.0,
+        };
+
+        self.max_acceleration * (free_road - interaction)
+    }
+}
+
+/// MOBIL lane changes: move over when it gains more acceleration than it
+/// costs the cars around, weighted by politeness, and never force the new
+/// follower to brake harder than `safe_deceleration`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mobil {
+    /// 0 cuts in whenever it pays off; 1 weighs the others as much as itself.
+    pub politeness: f64,
+    /// Least gain in acceleration worth a lane change.
+    pub threshold: f64,
+    pub safe_deceleration: f64,
+    /// Ticks to wait after a lane change before considering another.
+    pub cooldown: usize,
+}
+
+impl Default for Mobil {
+    fn default() -> Self {
+        Self {
+            politeness: 0.3,
+            threshold: 0.02,
+            safe_deceleration: 0.3,
+            cooldown: 120,
+        }
+    }
+}
+
+/// What an agent knows about another car on the road.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbor {
+    pub station: f64,
+    pub lane: usize,
+    pub speed: f64,
+    pub desired_speed: f64,
+    pub length: f64,
+}
+
+impl Neighbor {
+    pub fn of(car: &Car, road: &Road) -> Self {
+        Self {
+            station: car.stats.station,
+            lane: road.nearest_lane(car.stats.lateral),
+            speed: car.speed,
+            desired_speed: car.max_speed,
+            length: car.height,
+        }
+    }
+
+    fn gap_to(&self, ahead: &Neighbor) -> f64 {
+        ahead.station - self.station - (self.length + ahead.length) / 2.0
+    }
+}
+
+/// A traffic driver following the car ahead with [`Idm`] and changing lanes
+/// with [`Mobil`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Agent {
+    pub idm: Idm,
+    pub mobil: Mobil,
+    /// Lane the agent is in or moving over to.
+    pub lane: usize,
+    cooldown: usize,
+}
+
+impl Agent {
+    /// Controls for the next tick of the car at `neighbors[index]`.
+    pub fn drive(
+        &mut self,
+        car: &Car,
+        road: &Road,
+        neighbors: &[Neighbor],
+        index: usize,
+    ) -> Controls {
+        let me = Neighbor {
+            lane: self.lane,
+            ..neighbors[index]
+        };
+        let others = || {
+            neighbors
+                .iter()
+                .enumerate()
+                .filter(move |&(i, _)| i != index)
+                .map(|(_, other)| other)
+        };
+        let leader = |lane: usize, of: &Neighbor| {
+            others()
+                .filter(|other| other.lane == lane && other.station > of.station)
+                .min_by(|a, b| a.station.total_cmp(&b.station))
+                .copied()
+        };
+        let follower = |lane: usize| {
+            others()
+                .filter(|other| other.lane == lane && other.station <= me.station)
+                .max_by(|a, b| a.station.total_cmp(&b.station))
+                .copied()
+        };
+        let accelerate = |car: &Neighbor, ahead: Option<Neighbor>| {
+            self.idm.acceleration(
+                car.speed,
+                car.desired_speed,
+                ahead.map(|ahead| (car.gap_to(&ahead), ahead.speed)),
+            )
+        };
+
+        self.cooldown = self.cooldown.saturating_sub(1);
+        let ahead = leader(me.lane, &me);
+        let mut acceleration = accelerate(&me, ahead);
+
+        if self.cooldown == 0 {
+            let old_follower = follower(me.lane);
+            let old_follower_gain =
+                old_follower.map_or(0.0, |f| accelerate(&f, ahead) - accelerate(&f, Some(me)));
+
+            let mut best = (self.mobil.threshold, None);
+            for lane in [me.lane.wrapping_sub(1), me.lane + 1] {
+                if lane >= road.lane_count {
+                    continue;
+                }
+
+                let new_leader = leader(lane, &me);
+                let new_follower = follower(lane);
+                let fits = new_leader.is_none_or(|l| me.gap_to(&l) > 0.0)
+                    && new_follower.is_none_or(|f| f.gap_to(&me) > 0.0);
+                let follower_after = new_follower.map_or(0.0, |f| accelerate(&f, Some(me)));
+                if !fits || follower_after < -self.mobil.safe_deceleration {
+                    continue;
+                }
+
+                let own = accelerate(&me, new_leader);
+                let new_follower_gain =
+                    new_follower.map_or(0.0, |f| follower_after - accelerate(&f, new_leader));
+                let incentive = own - acceleration
+                    + self.mobil.politeness * (new_follower_gain + old_follower_gain);
+
+                if incentive > best.0 {
+                    best = (incentive, Some((lane, own)));
+                }
+            }
+
+            if let Some((lane, own)) = best.1 {
+                self.lane = lane;
+                self.cooldown = self.mobil.cooldown;
+                acceleration = own;
+            }
+        }
+
+        // Friction slows the car every tick, so holding a speed takes some throttle.
+        let force = (acceleration + car.friction) / car.acceleration;
+        Controls {
+            throttle: force.clamp(0.0, 1.0),
+            // Never brake past a standstill, which would reverse.
+            brake: (-force).clamp(0.0, car.speed.max(0.0) / car.acceleration),
+            steering: steer_to_lane(car, road, car.stats.station, car.stats.lateral, self.lane),
+        }
+    }
+}
+
+/// Steering that brings a car at `station` and `lateral` onto the center of
+/// `lane` and lines it up with the road ahead.
+pub fn steer_to_lane(car: &Car, road: &Road, station: f64, lateral: f64, lane: usize) -> f64 {
+    let heading = road.point_at(station + car.speed * 10.0).1;
+    let offset = lateral - road.lane_offset(lane);
+
+    (20.0 * (heading - car.angle) + 0.05 * offset).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Agent, Idm, Neighbor};
+    use crate::{car::Car, controls::ControlKind, road::Road, world::World};
+
+    fn agent_behind(road: &Road, blocked_lanes: &[usize]) -> (Agent, Car, Vec<Neighbor>) {
+        let mut car = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::Agent, None, None, None);
+        World::place(&mut car, road, 1, 0.0);
+        car.speed = 2.0;
+
+        let mut neighbors = vec![Neighbor::of(&car, road)];
+        for &lane in blocked_lanes {
+            neighbors.push(Neighbor {
+                station: 100.0,
+                lane,
+                speed: 0.0,
+                desired_speed: 2.0,
+                length: 50.0,
+            });
+        }
+        (car.agent.unwrap(), car, neighbors)
+    }
+
+    #[test]
+    fn test_idm_brakes_for_slower_leader() {
+        let idm = Idm::default();
+
+        assert!(idm.acceleration(0.0, 2.0, None) > 0.0);
+        assert!(idm.acceleration(2.0, 2.0, None).abs() < 1e-12);
+        assert!(idm.acceleration(2.0, 2.0, Some((30.0, 0.0))) < -idm.comfortable_deceleration);
+        assert!(idm.acceleration(1.0, 2.0, Some((500.0, 2.0))) > 0.0);
+    }
+
+    #[test]
+    fn test_agent_brakes_when_boxed_in() {
+        let road = Road::new(100.0, 180.0, None);
+        let (mut agent, car, neighbors) = agent_behind(&road, &[0, 1, 2]);
+
+        let controls = agent.drive(&car, &road, &neighbors, 0);
+        assert_eq!(agent.lane, 1);
+        assert_eq!(controls.throttle, 0.0);
+        assert!(controls.brake > 0.0);
+    }
+
+    #[test]
+    fn test_agent_overtakes_through_free_lane() {
+        let road = Road::new(100.0, 180.0, None);
+        let (mut agent, car, neighbors) = agent_behind(&road, &[1, 2]);
+
+        agent.drive(&car, &road, &neighbors, 0);
+        assert_eq!(agent.lane, 0);
+    }
+}
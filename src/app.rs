@@ -1,18 +1,32 @@
 use gloo::{
+    dialogs::{alert, confirm, prompt},
+    file::{
+        callbacks::{read_as_bytes, read_as_text, FileReader},
+        File,
+    },
     render::{request_animation_frame, AnimationFrame},
-    utils::{document, format::JsValueSerdeExt, window},
+    utils::{document, window},
 };
-use js_sys::JSON;
+use js_sys::{encode_uri_component, Date};
+use rand::{thread_rng, Rng};
 use wasm_bindgen::JsCast;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, Storage};
-use yew::{html, Callback, Component};
+use web_sys::{
+    CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement, HtmlInputElement,
+    HtmlSelectElement, Storage,
+};
+use yew::{html, Callback, Component, Event, TargetCast};
 
 use crate::{
-    car::{Car, CarPtr},
     controls::ControlKind,
-    road::Road,
-    utils::get_random_color,
-    visualizer,
+    fitness,
+    library::{BrainLibrary, BrainSlot, SlotId, SlotMetadata},
+    network::NeuralNetwork,
+    render::{self, SpriteCache},
+    scenario::{RoadGeometry, RoadSpec, Scenario},
+    sensor,
+    traffic::{TrafficConfig, TrafficSpawner},
+    vehicle, visualizer,
+    world::{Interaction, World},
 };
 
 #[derive(Debug, Default)]
@@ -21,6 +35,24 @@ pub enum Msg {
     None,
     Save,
     Discard,
+    SetTickBudget(usize),
+    SetFitness(String),
+    SetSeed(u64),
+    SetVehicle(String),
+    SetSensor(String),
+    SetRoad(String),
+    SetTraffic(String),
+    SetInteraction(String),
+    OpenScenario(File),
+    LoadScenario(Result<String, String>),
+    LoadSlot(SlotId),
+    RenameSlot(String),
+    DeleteSlot(SlotId),
+    ToggleCompare(SlotId),
+    ExportBrain(Option<SlotId>),
+    OpenBrain(File),
+    /// The file's name and contents.
+    ImportBrain(String, Result<Vec<u8>, String>),
     AnimationFrame(f64),
 }
 
@@ -30,63 +62,171 @@ pub struct App {
     network_ctx: Option<CanvasRenderingContext2d>,
     car_canvas: Option<HtmlCanvasElement>,
     car_ctx: Option<CanvasRenderingContext2d>,
-    traffic: Vec<CarPtr>,
-    road: Road,
+    sprites: SpriteCache,
     animation: Option<AnimationFrame>,
 
-    cars: Vec<CarPtr>,
-    best_car: Option<CarPtr>,
+    world: World,
     storage: Option<Storage>,
+    reader: Option<FileReader>,
+    library: BrainLibrary,
+    /// Up to two slots shown side by side, oldest pick first.
+    comparing: Vec<SlotId>,
 }
 
+const LIBRARY_KEY: &str = "brain_library";
+
 impl App {
-    fn generate_cars(&self, amount: usize) -> Vec<CarPtr> {
-        let mut cars = Vec::new();
-        for _ in 1..=amount {
-            cars.push(Car::new(
-                self.road.get_late_center(1),
-                100.0,
-                30.0,
-                50.0,
-                ControlKind::AI,
-                None,
-                None,
-            ));
+    /// Reads the brain library, taking over the single brain saved before
+    /// there was one.
+    fn open_library(&mut self) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+
+        if let Ok(Some(item)) = storage.get_item(LIBRARY_KEY) {
+            match serde_json::from_str(&item) {
+                Ok(library) => self.library = library,
+                Err(err) => alert(&format!("could not read the saved brains: {err}")),
+            }
+        } else if let Ok(Some(item)) = storage.get_item("best_brain") {
+            // Written as `null` when there was no brain to save.
+            if item != "null" {
+                match NeuralNetwork::from_json(&item) {
+                    Ok(brain) => {
+                        self.library.autosave(brain, None);
+                        self.store_library();
+                    }
+                    Err(err) => alert(&format!("could not read the saved brain: {err}")),
+                }
+            }
+            storage.remove_item("best_brain").unwrap();
         }
+    }
 
-        cars
+    fn store_library(&self) {
+        if let Some(storage) = &self.storage {
+            storage
+                .set_item(LIBRARY_KEY, &serde_json::to_string(&self.library).unwrap())
+                .unwrap();
+        }
     }
 
-    pub fn save(&self) {
-        if let Some(best_car) = &self.best_car {
-            self.storage.as_ref().map(|storage| {
-                storage
-                    .set_item(
-                        "best_brain",
-                        JSON::stringify(
-                            &<::wasm_bindgen::JsValue as JsValueSerdeExt>::from_serde(
-                                &best_car.brain,
-                            )
-                            .unwrap(),
-                        )
-                        .unwrap()
-                        .as_string()
-                        .unwrap()
-                        .as_str(),
-                    )
-                    .unwrap();
-                Some(())
-            });
+    /// Where the current best brain comes from.
+    fn metadata(&self) -> Option<SlotMetadata> {
+        self.world.best_car().map(|best_car| SlotMetadata {
+            generation: self.world.generation,
+            fitness: best_car.fitness,
+            fitness_name: self.world.fitness.name().to_string(),
+            seed: self.world.seed,
+            saved_at: Date::new_0().to_iso_string().into(),
+        })
+    }
+
+    fn best_brain(&self) -> Option<NeuralNetwork> {
+        self.world.best_car().and_then(|car| car.brain.clone())
+    }
+
+    /// Saves the best brain as a new version of a slot named by the user.
+    pub fn save(&mut self) {
+        let Some(brain) = self.best_brain() else {
+            return;
+        };
+        let default = self
+            .library
+            .active
+            .as_ref()
+            .map_or("brain", |id| id.name.as_str())
+            .to_string();
+
+        if let Some(name) = prompt("Save the best brain as", Some(&default)) {
+            let name = name.trim();
+            if !name.is_empty() {
+                let metadata = self.metadata();
+                match self.library.save(name, brain, metadata) {
+                    Ok(_) => self.store_library(),
+                    Err(err) => alert(&err.to_string()),
+                }
+            }
         }
     }
 
+    fn autosave(&mut self) {
+        if let Some(brain) = self.best_brain() {
+            let metadata = self.metadata();
+            self.library.autosave(brain, metadata);
+            self.store_library();
+        }
+    }
+
+    /// Forgets the brain new populations start from; named slots are kept.
     pub fn discard(&mut self) {
-        if let Some(storage) = &self.storage {
-            storage.remove_item("best_brain").unwrap();
+        self.library.discard();
+        self.store_library();
+    }
+
+    /// Downloads the slot `id`, or the current best brain without one.
+    fn export(&self, id: Option<SlotId>) {
+        let (brain, filename) = match &id {
+            Some(id) => match self.library.get(id) {
+                Some(slot) => (
+                    slot.brain.clone(),
+                    format!("{}-v{}.json", slot.id.name, slot.id.version),
+                ),
+                None => return,
+            },
+            None => match self.best_brain() {
+                Some(brain) => (brain, "best_brain.json".to_string()),
+                None => return,
+            },
+        };
+
+        let anchor = document()
+            .create_element("a")
+            .unwrap()
+            .dyn_into::<HtmlAnchorElement>()
+            .unwrap();
+        anchor.set_href(&format!(
+            "data:application/json;charset=utf-8,{}",
+            encode_uri_component(&brain.to_json())
+        ));
+        anchor.set_download(&filename);
+        anchor.click();
+    }
+
+    fn traffic_driver(&self) -> Option<ControlKind> {
+        self.world
+            .spawner
+            .as_ref()
+            .map(|spawner| spawner.config.driver)
+    }
+
+    /// Starts over from generation zero with a fresh population drawn from
+    /// `seed`, keeping the current settings and starting from the library's
+    /// active brain.
+    fn restart(&mut self, seed: u64) {
+        self.world = self.world.restarted(seed);
+
+        // A brain trained with another sensor would bring that sensor along.
+        if let Some(brain) = self
+            .library
+            .starting_brain()
+            .filter(|brain| brain.sensor == self.world.brain.sensor)
+        {
+            if let Err(err) = self.world.load_brain(brain, 0.1) {
+                alert(&format!("starting from random brains: {err}"));
+            }
         }
     }
 
     fn animate(&mut self, time: f64) {
+        // Frames are only requested once the canvases exist.
+        self.world.step();
+
+        if self.world.generation_over() {
+            self.autosave();
+            self.world.next_generation();
+        }
+
         let car_canvas = match &self.car_canvas {
             Some(value) => value,
             None => return,
@@ -107,53 +247,45 @@ impl App {
             None => return,
         };
 
-        for i in 0..self.traffic.len() {
-            self.traffic[i].update(&self.road.borders, &Vec::new());
-        }
-
-        for i in 0..self.cars.len() {
-            self.cars[i].update(&self.road.borders, &self.traffic);
-        }
-
-        self.best_car = Some(
-            self.cars
-                .iter()
-                .min_by(|a, b| a.y.partial_cmp(&b.y).unwrap())
-                .unwrap()
-                .clone(),
-        );
-
         car_canvas.set_height(window().inner_height().unwrap().as_f64().unwrap() as u32);
         network_canvas.set_height(window().inner_height().unwrap().as_f64().unwrap() as u32);
 
         car_ctx.save();
-        if let Some(best_car) = &self.best_car {
+        if let Some(best_car) = self.world.best_car() {
+            let (center, _) = self.world.road.point_at(best_car.stats.station);
             car_ctx
-                .translate(0.0, -best_car.y + car_canvas.height() as f64 * 0.7)
+                .translate(
+                    -center.x + car_canvas.width() as f64 / 2.0,
+                    -best_car.y + car_canvas.height() as f64 * 0.7,
+                )
                 .unwrap()
         }
 
-        self.road.draw(car_ctx);
+        render::draw_road(car_ctx, &self.world.road);
 
-        for i in 0..self.traffic.len() {
-            self.traffic[i].draw(car_ctx, None);
+        let traffic = match self.world.best_car() {
+            Some(best_car) => self.world.traffic_of(best_car).0,
+            None => &self.world.traffic,
+        };
+        for car in traffic {
+            render::draw_car(car_ctx, car, self.sprites.get(car), None);
         }
 
         car_ctx.set_global_alpha(0.2);
-        for i in 0..self.cars.len() {
-            self.cars[i].draw(car_ctx, None);
+        for car in &self.world.cars {
+            render::draw_car(car_ctx, car, self.sprites.get(car), None);
         }
 
         car_ctx.set_global_alpha(1.0);
 
-        if let Some(best_car) = &mut self.best_car {
-            best_car.draw(car_ctx, Some(true))
+        if let Some(best_car) = self.world.best_car() {
+            render::draw_car(car_ctx, best_car, self.sprites.get(best_car), Some(true))
         }
 
         car_ctx.restore();
 
         network_ctx.set_line_dash_offset(-time / 50.0);
-        if let Some(best_car) = &self.best_car {
+        if let Some(best_car) = self.world.best_car() {
             if let Some(brain) = &best_car.brain {
                 visualizer::draw_network(network_ctx, brain);
             }
@@ -161,6 +293,115 @@ impl App {
     }
 }
 
+impl App {
+    /// Every saved brain, with buttons to load, rename, delete and compare it.
+    fn view_library(&self, ctx: &yew::Context<Self>) -> yew::Html {
+        let row = |slot: &BrainSlot| {
+            let id = slot.id.clone();
+            let active = self.library.active.as_ref() == Some(&id);
+            let (generation, fitness, seed, saved_at) = match &slot.metadata {
+                Some(metadata) => (
+                    metadata.generation.to_string(),
+                    format!("{:.1} {}", metadata.fitness, metadata.fitness_name),
+                    metadata.seed.to_string(),
+                    metadata
+                        .saved_at
+                        .replace('T', " ")
+                        .chars()
+                        .take(16)
+                        .collect::<String>(),
+                ),
+                None => Default::default(),
+            };
+            let shape = slot
+                .shape()
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join("-");
+
+            html! {
+                <tr class={active.then_some("active")}>
+                    <td>
+                        <input
+                            type="checkbox"
+                            title="Compare"
+                            checked={self.comparing.contains(&id)}
+                            onchange={ctx.link().callback({
+                                let id = id.clone();
+                                move |_| Msg::ToggleCompare(id.clone())
+                            })}
+                        />
+                    </td>
+                    <td>{format!("{} v{}", id.name, id.version)}</td>
+                    <td>{generation}</td>
+                    <td>{fitness}</td>
+                    <td>{shape}</td>
+                    <td>{seed}</td>
+                    <td>{saved_at}</td>
+                    <td>
+                        <button title="Load" onclick={ctx.link().callback({
+                            let id = id.clone();
+                            move |_| Msg::LoadSlot(id.clone())
+                        })}>{"▶️"}</button>
+                        <button title="Rename" onclick={ctx.link().callback({
+                            let name = id.name.clone();
+                            move |_| Msg::RenameSlot(name.clone())
+                        })}>{"✏️"}</button>
+                        <button title="Download" onclick={ctx.link().callback({
+                            let id = id.clone();
+                            move |_| Msg::ExportBrain(Some(id.clone()))
+                        })}>{"⬇️"}</button>
+                        <button title="Delete" onclick={ctx.link().callback({
+                            let id = id.clone();
+                            move |_| Msg::DeleteSlot(id.clone())
+                        })}>{"❌"}</button>
+                    </td>
+                </tr>
+            }
+        };
+
+        let comparison = match self.comparing.as_slice() {
+            [a, b] => match self.library.compare(a, b) {
+                Ok(comparison) => {
+                    let distance = comparison
+                        .parameter_distance
+                        .map_or_else(|| "different shapes".to_string(), |d| format!("{d:.3}"));
+                    let gain = comparison
+                        .fitness_gain
+                        .map_or_else(|| "not comparable".to_string(), |g| format!("{g:+.1}"));
+                    format!(
+                        "{} v{} → {} v{}: parameter distance {distance}, fitness {gain}",
+                        a.name, a.version, b.name, b.version
+                    )
+                }
+                Err(err) => err.to_string(),
+            },
+            _ => "Tick two brains to compare them.".to_string(),
+        };
+
+        html! {
+            <details id="library">
+                <summary title="Saved brains">{"📚"}</summary>
+                <table>
+                    <tr>
+                        <th></th>
+                        <th>{"brain"}</th>
+                        <th>{"generation"}</th>
+                        <th>{"fitness"}</th>
+                        <th>{"shape"}</th>
+                        <th>{"seed"}</th>
+                        <th>{"saved"}</th>
+                        <th></th>
+                    </tr>
+                    { for self.library.slots.iter().map(row) }
+                </table>
+                <p>{comparison}</p>
+            </details>
+        }
+    }
+}
+
 impl Component for App {
     type Message = Msg;
 
@@ -183,6 +424,89 @@ impl Component for App {
                 link.send_message(Msg::Discard);
             })
         };
+        let export_button_onclick = ctx.link().callback(|_| Msg::ExportBrain(None));
+        let import_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let input = event.target_unchecked_into::<HtmlInputElement>();
+                if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                    link.send_message(Msg::OpenBrain(File::from(file)));
+                }
+                input.set_value("");
+            })
+        };
+        let tick_budget_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let input = event.target_unchecked_into::<HtmlInputElement>();
+                if let Ok(tick_budget) = input.value().parse() {
+                    link.send_message(Msg::SetTickBudget(tick_budget));
+                }
+            })
+        };
+
+        let scenario_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let input = event.target_unchecked_into::<HtmlInputElement>();
+                if let Some(file) = input.files().and_then(|files| files.get(0)) {
+                    link.send_message(Msg::OpenScenario(File::from(file)));
+                }
+                // Picking the same file again should load it again.
+                input.set_value("");
+            })
+        };
+        let seed_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let input = event.target_unchecked_into::<HtmlInputElement>();
+                if let Ok(seed) = input.value().parse() {
+                    link.send_message(Msg::SetSeed(seed));
+                }
+            })
+        };
+        let vehicle_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetVehicle(select.value()));
+            })
+        };
+        let sensor_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetSensor(select.value()));
+            })
+        };
+        let road_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetRoad(select.value()));
+            })
+        };
+        let traffic_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetTraffic(select.value()));
+            })
+        };
+        let interaction_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetInteraction(select.value()));
+            })
+        };
+        let fitness_onchange = {
+            let link = ctx.link().clone();
+            Callback::from(move |event: Event| {
+                let select = event.target_unchecked_into::<HtmlSelectElement>();
+                link.send_message(Msg::SetFitness(select.value()));
+            })
+        };
 
         html! {
             <>
@@ -190,17 +514,255 @@ impl Component for App {
                 <div id="verticalButtons">
                     <button id="saveButton" onclick={save_button_onclick}>{"💾"}</button>
                         <button id="discardButton" onclick={discard_button_onclick}>{"🗑️"}</button>
+                    <button id="exportButton" title="Download the best brain" onclick={export_button_onclick}>{"⬇️"}</button>
+                    <label id="importBrain" title="Load a brain file">
+                        {"⬆️"}
+                        <input type="file" accept=".json,.bin" onchange={import_onchange} />
+                    </label>
+                    <span id="generation" title="Generation">{format!("#{}", self.world.generation)}</span>
+                    <input
+                        id="tickBudget"
+                        type="number"
+                        min="1"
+                        title="Ticks per generation"
+                        value={self.world.config.tick_budget.to_string()}
+                        onchange={tick_budget_onchange}
+                    />
+                    <select id="fitness" title="Fitness" onchange={fitness_onchange}>
+                        { for fitness::built_in().iter().map(|fitness| html! {
+                            <option
+                                value={fitness.name()}
+                                selected={fitness.name() == self.world.fitness.name()}
+                            >
+                                {fitness.name()}
+                            </option>
+                        }) }
+                    </select>
+                    <select id="vehicle" title="Vehicle model" onchange={vehicle_onchange}>
+                        { for vehicle::built_in().iter().map(|vehicle| html! {
+                            <option
+                                value={vehicle.name()}
+                                selected={vehicle.name() == self.world.vehicle.name()}
+                            >
+                                {vehicle.name()}
+                            </option>
+                        }) }
+                    </select>
+                    <select id="sensor" title="Sensor" onchange={sensor_onchange}>
+                        { for sensor::built_in().iter().map(|&(name, config)| html! {
+                            <option value={name} selected={config == self.world.brain.sensor}>
+                                {name}
+                            </option>
+                        }) }
+                        if sensor::name_of(&self.world.brain.sensor).is_none() {
+                            <option selected=true disabled=true>{"custom"}</option>
+                        }
+                    </select>
+                    <select id="road" title="Road" onchange={road_onchange}>
+                        <option value="straight" selected={!self.world.road.is_procedural()}>
+                            {"straight"}
+                        </option>
+                        <option value="curvy" selected={self.world.road.is_procedural()}>
+                            {"curvy"}
+                        </option>
+                    </select>
+                    <select id="traffic" title="Traffic" onchange={traffic_onchange}>
+                        <option value="endless" selected={self.traffic_driver() == Some(ControlKind::Dummy)}>
+                            {"endless"}
+                        </option>
+                        <option value="agents" selected={self.traffic_driver() == Some(ControlKind::Agent)}>
+                            {"agents"}
+                        </option>
+                        <option value="fixed" selected={self.world.spawner.is_none()}>
+                            {"fixed"}
+                        </option>
+                    </select>
+                    <select id="interaction" title="How AI cars share the road" onchange={interaction_onchange}>
+                        { for Interaction::ALL.iter().map(|interaction| html! {
+                            <option
+                                value={interaction.name()}
+                                selected={*interaction == self.world.interaction}
+                            >
+                                {interaction.name()}
+                            </option>
+                        }) }
+                    </select>
+                    <label id="scenario" title="Load a scenario (JSON or RON)">
+                        {"📂"}
+                        <input type="file" accept=".json,.ron" onchange={scenario_onchange} />
+                    </label>
+                    <input
+                        id="seed"
+                        type="number"
+                        min="0"
+                        title="Seed"
+                        value={self.world.seed.to_string()}
+                        onchange={seed_onchange}
+                    />
+                    { self.view_library(ctx) }
                 </div>
                 <canvas id="networkCanvas"></canvas>
             </>
         }
     }
 
-    fn update(&mut self, _: &yew::Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &yew::Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::None => (),
             Msg::Save => self.save(),
             Msg::Discard => self.discard(),
+            Msg::SetTickBudget(tick_budget) => self.world.config.tick_budget = tick_budget,
+            Msg::SetFitness(name) => {
+                if let Some(fitness) = fitness::from_name(&name) {
+                    self.world.fitness = fitness;
+                }
+            }
+            Msg::SetSeed(seed) => self.restart(seed),
+            Msg::SetVehicle(name) => {
+                if let Some(vehicle) = vehicle::from_name(&name) {
+                    self.world.vehicle = vehicle;
+                    let seed = self.world.seed;
+                    self.restart(seed);
+                }
+            }
+            Msg::SetSensor(name) => {
+                if let Some(config) = sensor::from_name(&name) {
+                    self.world.brain.sensor = config;
+                    let seed = self.world.seed;
+                    self.restart(seed);
+                }
+            }
+            Msg::SetRoad(name) => {
+                self.world.scenario.road.geometry = match name.as_str() {
+                    "curvy" => RoadGeometry::Procedural { seed: None },
+                    _ => RoadGeometry::Straight,
+                };
+                let seed = self.world.seed;
+                self.restart(seed);
+            }
+            Msg::OpenScenario(file) => {
+                let link = ctx.link().clone();
+                self.reader = Some(read_as_text(&file, move |text| {
+                    link.send_message(Msg::LoadScenario(text.map_err(|err| err.to_string())))
+                }));
+            }
+            Msg::LoadScenario(text) => {
+                self.reader = None;
+                match text
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| Scenario::parse(&text).map_err(|err| err.to_string()))
+                {
+                    Ok(scenario) => {
+                        // A scenario brings its own traffic.
+                        self.world.scenario = scenario;
+                        self.world.spawner = None;
+                        let seed = self.world.seed;
+                        self.restart(seed);
+                    }
+                    Err(err) => alert(&err),
+                }
+            }
+            Msg::SetTraffic(name) => {
+                let seed = self.world.seed;
+                let driver = match name.as_str() {
+                    "fixed" => None,
+                    "agents" => Some(ControlKind::Agent),
+                    _ => Some(ControlKind::Dummy),
+                };
+                self.world.spawner = driver.map(|driver| {
+                    let config = TrafficConfig {
+                        driver,
+                        ..Default::default()
+                    };
+                    TrafficSpawner::new(config, seed)
+                });
+                self.restart(seed);
+            }
+            Msg::SetInteraction(name) => {
+                if let Ok(interaction) = name.parse() {
+                    self.world.interaction = interaction;
+                    let seed = self.world.seed;
+                    self.restart(seed);
+                }
+            }
+            Msg::LoadSlot(id) => match self.library.load(&id) {
+                Ok(brain) => {
+                    self.world.brain.sensor = brain.sensor;
+                    self.store_library();
+                    let seed = self.world.seed;
+                    self.restart(seed);
+                }
+                Err(err) => alert(&err.to_string()),
+            },
+            Msg::RenameSlot(name) => {
+                if let Some(new_name) = prompt(&format!("Rename {name} to"), Some(&name)) {
+                    let new_name = new_name.trim();
+                    if !new_name.is_empty() && new_name != name {
+                        match self.library.rename(&name, new_name) {
+                            Ok(()) => {
+                                for id in self.comparing.iter_mut().filter(|id| id.name == name) {
+                                    id.name = new_name.to_string();
+                                }
+                                self.store_library();
+                            }
+                            Err(err) => alert(&err.to_string()),
+                        }
+                    }
+                }
+            }
+            Msg::DeleteSlot(id) => {
+                if confirm(&format!("Delete {} v{}?", id.name, id.version)) {
+                    match self.library.delete(&id) {
+                        Ok(_) => {
+                            self.comparing.retain(|other| other != &id);
+                            self.store_library();
+                        }
+                        Err(err) => alert(&err.to_string()),
+                    }
+                }
+            }
+            Msg::ToggleCompare(id) => {
+                if let Some(index) = self.comparing.iter().position(|other| other == &id) {
+                    self.comparing.remove(index);
+                } else {
+                    if self.comparing.len() == 2 {
+                        self.comparing.remove(0);
+                    }
+                    self.comparing.push(id);
+                }
+            }
+            Msg::ExportBrain(id) => self.export(id),
+            Msg::OpenBrain(file) => {
+                let link = ctx.link().clone();
+                let name = file.name();
+                self.reader = Some(read_as_bytes(&file, move |bytes| {
+                    link.send_message(Msg::ImportBrain(name, bytes.map_err(|err| err.to_string())))
+                }));
+            }
+            Msg::ImportBrain(name, bytes) => {
+                self.reader = None;
+                match bytes
+                    .and_then(|bytes| NeuralNetwork::decode(&bytes).map_err(|err| err.to_string()))
+                {
+                    Ok(brain) => {
+                        // Imported brains join the library, and training continues from them.
+                        let name = name
+                            .strip_suffix(".json")
+                            .or_else(|| name.strip_suffix(".bin"))
+                            .unwrap_or(&name);
+                        let sensor = brain.sensor;
+                        if let Err(err) = self.library.save(name, brain, None) {
+                            alert(&err.to_string());
+                            return true;
+                        }
+                        self.world.brain.sensor = sensor;
+                        self.store_library();
+                        let seed = self.world.seed;
+                        self.restart(seed);
+                    }
+                    Err(err) => alert(&err),
+                }
+            }
             Msg::AnimationFrame(time) => self.animate(time),
         }
         true
@@ -227,11 +789,12 @@ impl Component for App {
                         .unwrap(),
                 );
 
-                self.road = Road::new(
-                    car_canvas.width() as f64 / 2.0,
-                    car_canvas.width() as f64 * 0.9,
-                    None,
-                );
+                self.world.scenario.road = RoadSpec {
+                    x: car_canvas.width() as f64 / 2.0,
+                    width: car_canvas.width() as f64 * 0.9,
+                    ..Default::default()
+                };
+                self.world.spawner = Some(TrafficSpawner::new(TrafficConfig::default(), 0));
             }
 
             self.network_canvas = Some(
@@ -254,90 +817,10 @@ impl Component for App {
                 );
             }
 
-            self.cars = self.generate_cars(100);
-
-            self.traffic = vec![
-                Car::new(
-                    self.road.get_late_center(1),
-                    -100.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(0),
-                    -300.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(2),
-                    -300.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(0),
-                    -500.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(1),
-                    -500.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(1),
-                    -700.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-                Car::new(
-                    self.road.get_late_center(2),
-                    -700.0,
-                    30.0,
-                    50.0,
-                    ControlKind::Dummy,
-                    Some(2.0),
-                    Some(&get_random_color()),
-                ),
-            ];
-
             self.storage = web_sys::window().unwrap().local_storage().unwrap();
+            self.open_library();
 
-            if let Some(storage) = &self.storage {
-                if let Ok(Some(item)) = storage.get_item("best_brain") {
-                    for i in 0..self.cars.len() {
-                        if i == 0 {
-                            self.cars[i].brain = gloo::utils::format::JsValueSerdeExt::into_serde(
-                                &JSON::parse(&item).unwrap(),
-                            )
-                            .unwrap()
-                        } else if let Some(brain) = &mut self.cars[i].brain {
-                            brain.mutate(Some(0.1))
-                        }
-                    }
-                }
-            }
+            self.restart(thread_rng().gen::<u32>() as u64);
         }
 
         {
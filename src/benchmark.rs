@@ -0,0 +1,280 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    brain::BrainError, damage::DamageModel, network::NeuralNetwork, noise::NoiseConfig,
+    scenario::Scenario, vehicle::Vehicle, world::World,
+};
+
+/// What a brain has to achieve in a [`BenchmarkCase`] to pass it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Criteria {
+    pub min_distance: f64,
+    pub max_collisions: usize,
+    pub max_lane_violations: usize,
+}
+
+impl Default for Criteria {
+    fn default() -> Self {
+        Self {
+            min_distance: 1_000.0,
+            max_collisions: 0,
+            max_lane_violations: 10,
+        }
+    }
+}
+
+/// One scenario, driven by a single car for at most `ticks`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkCase {
+    pub scenario: Scenario,
+    pub ticks: usize,
+    pub criteria: Criteria,
+}
+
+impl BenchmarkCase {
+    pub fn new(scenario: Scenario, ticks: usize, criteria: Criteria) -> Self {
+        Self {
+            scenario,
+            ticks,
+            criteria,
+        }
+    }
+}
+
+/// The shipped suite, read from `scenarios/benchmark`.
+pub fn standard_suite() -> Vec<BenchmarkCase> {
+    let case = |text: &str, min_distance: f64, max_lane_violations: usize| {
+        BenchmarkCase::new(
+            Scenario::parse(text).unwrap(),
+            2_000,
+            Criteria {
+                min_distance,
+                max_collisions: 0,
+                max_lane_violations,
+            },
+        )
+    };
+
+    vec![
+        case(
+            include_str!("../scenarios/benchmark/empty-road.ron"),
+            3_000.0,
+            2,
+        ),
+        // Following the leader gets about 2000, so passing takes overtaking it.
+        case(
+            include_str!("../scenarios/benchmark/slow-leader.ron"),
+            2_500.0,
+            4,
+        ),
+        case(
+            include_str!("../scenarios/benchmark/blocked-lanes.ron"),
+            1_200.0,
+            10,
+        ),
+        case(
+            include_str!("../scenarios/benchmark/dense-traffic.ron"),
+            3_000.0,
+            10,
+        ),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub name: String,
+    pub ticks: usize,
+    pub distance: f64,
+    /// Ticks spent scraping, plus one for the crash if the car was damaged.
+    pub collisions: usize,
+    /// Tick the car was damaged at, if it was.
+    pub damaged_at: Option<usize>,
+    pub lane_violations: usize,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub cases: Vec<CaseReport>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(|case| case.passed)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Every way this report is worse than `baseline`: a case that no longer
+    /// passes, gets less far or collides more often. Cases missing from
+    /// either report are ignored.
+    pub fn regressions(&self, baseline: &Report) -> Vec<String> {
+        let mut regressions = Vec::new();
+
+        for case in &self.cases {
+            let Some(old) = baseline.cases.iter().find(|old| old.name == case.name) else {
+                continue;
+            };
+
+            if old.passed && !case.passed {
+                regressions.push(format!("{}: no longer passes", case.name));
+            }
+            if case.distance < old.distance - 1.0 {
+                regressions.push(format!(
+                    "{}: distance fell from {:.1} to {:.1}",
+                    case.name, old.distance, case.distance
+                ));
+            }
+            if case.collisions > old.collisions {
+                regressions.push(format!(
+                    "{}: collisions rose from {} to {}",
+                    case.name, old.collisions, case.collisions
+                ));
+            }
+        }
+
+        regressions
+    }
+}
+
+impl fmt::Display for Report {
+    /// A plain text table, one row per case.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:>6} {:>9} {:>10} {:>10} {:>15} {:>6}",
+            "scenario",
+            "ticks",
+            "distance",
+            "collisions",
+            "damaged at",
+            "lane violations",
+            "result"
+        )?;
+        for case in &self.cases {
+            let damaged_at = case
+                .damaged_at
+                .map_or_else(|| "-".to_string(), |tick| tick.to_string());
+            writeln!(
+                f,
+                "{:<16} {:>6} {:>9.1} {:>10} {:>10} {:>15} {:>6}",
+                case.name,
+                case.ticks,
+                case.distance,
+                case.collisions,
+                damaged_at,
+                case.lane_violations,
+                if case.passed { "pass" } else { "FAIL" }
+            )?;
+        }
+        write!(
+            f,
+            "{} of {} passed",
+            self.cases.iter().filter(|case| case.passed).count(),
+            self.cases.len()
+        )
+    }
+}
+
+/// Drives `brain`, unchanged, through `case` until the tick budget is spent
+/// or the car is damaged or stuck. Its sensor readings are degraded by
+/// `noise`, drawn from the same seed on every run, and `damage` tells its
+/// scrapes from a crash.
+pub fn run_case(
+    brain: &NeuralNetwork,
+    case: &BenchmarkCase,
+    vehicle: &Vehicle,
+    noise: NoiseConfig,
+    damage: DamageModel,
+) -> Result<CaseReport, BrainError> {
+    let mut world = World::new(case.scenario.road.build(0), Vec::new(), Vec::new(), 0);
+    world.scenario = case.scenario.clone();
+    world.vehicle = vehicle.clone();
+    world.noise = noise;
+    world.damage = damage;
+    world.config.tick_budget = case.ticks;
+    world.populate(1);
+    world.load_brain(brain, 0.0)?;
+
+    while !world.generation_over() {
+        world.step();
+    }
+
+    let car = &world.cars[0];
+    let report = CaseReport {
+        name: case.scenario.name.clone(),
+        ticks: world.tick,
+        distance: car.stats.distance(),
+        collisions: car.stats.scrapes + usize::from(car.damaged),
+        damaged_at: car.stats.damaged_at,
+        lane_violations: car.stats.lane_violations,
+        passed: false,
+    };
+    Ok(CaseReport {
+        passed: report.distance >= case.criteria.min_distance
+            && report.collisions <= case.criteria.max_collisions
+            && report.lane_violations <= case.criteria.max_lane_violations,
+        ..report
+    })
+}
+
+pub fn run(
+    brain: &NeuralNetwork,
+    cases: &[BenchmarkCase],
+    vehicle: &Vehicle,
+    noise: NoiseConfig,
+    damage: DamageModel,
+) -> Result<Report, BrainError> {
+    Ok(Report {
+        cases: cases
+            .iter()
+            .map(|case| run_case(brain, case, vehicle, noise, damage))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, standard_suite, Report};
+    use crate::{
+        damage::DamageModel, network::NeuralNetwork, noise::NoiseConfig, vehicle::Vehicle,
+    };
+
+    /// Regenerate `brains/reference-report.json` with
+    /// `cargo run --release --bin benchmark -- --json brains/reference.json`
+    /// whenever the reference brain is replaced by a better one.
+    #[test]
+    fn test_reference_brain_does_not_regress() {
+        let brain = NeuralNetwork::from_json(include_str!("../brains/reference.json")).unwrap();
+        let baseline: Report =
+            serde_json::from_str(include_str!("../brains/reference-report.json")).unwrap();
+
+        let report = run(
+            &brain,
+            &standard_suite(),
+            &Vehicle::default(),
+            NoiseConfig::default(),
+            DamageModel::default(),
+        )
+        .unwrap();
+        assert_eq!(report.cases.len(), baseline.cases.len());
+        assert_eq!(report.regressions(&baseline), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_regressions_are_reported() {
+        let baseline: Report =
+            serde_json::from_str(include_str!("../brains/reference-report.json")).unwrap();
+
+        let mut worse = baseline.clone();
+        worse.cases[0].distance -= 100.0;
+        worse.cases[0].collisions += 1;
+        worse.cases[0].passed = false;
+        assert_eq!(worse.regressions(&baseline).len(), 3);
+        assert!(baseline.regressions(&worse).is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{env, error::Error, fs, process};
+
+use self_driving_car::{
+    benchmark::{self, BenchmarkCase, Criteria, Report},
+    damage::DamageModel,
+    network::NeuralNetwork,
+    noise::NoiseConfig,
+    scenario::Scenario,
+    vehicle::{self, Vehicle},
+};
+
+const USAGE: &str = "\
+Usage: benchmark [OPTIONS] <BRAIN>
+
+Drives the brain in <BRAIN>, as written by the web UI or the trainer, through
+a set of scenarios and reports how far it got. Exits with 1 if any scenario
+fails or, with --baseline, if anything got worse than the baseline.
+
+Options:
+    --scenario <FILE>   scenario to run instead of the standard suite, may be repeated
+    --ticks <N>         tick budget per scenario (default: 2000)
+    --min-distance <F>  distance needed to pass a --scenario (default: 1000)
+    --vehicle <NAME>    arcade or bicycle vehicle model (default: arcade)
+    --range-noise <F>   standard deviation of the noise on sensor ranges, in ray lengths
+                        (default: 0)
+    --ray-dropout <P>   chance a sensor ray reads nothing on a tick (default: 0)
+    --range-levels <N>  distinct ranges a sensor ray reports, 0 for exact (default: 0)
+    --sensor-latency <N>
+                        ticks before the brain sees a sensor reading (default: 0)
+    --crash-speed <F>   impacts at least this fast disable the car, slower ones only scrape;
+                        0 makes every contact a crash (default: 0)
+    --scrape-slowdown <F>
+                        share of its speed the car loses in a scrape (default: 0.5)
+    --baseline <FILE>   JSON report of an earlier run to compare against; only
+                        regressions fail the run
+    --json              print the report as JSON instead of a table
+    --help              print this message";
+
+#[derive(Debug, Default)]
+struct Options {
+    brain: String,
+    scenarios: Vec<String>,
+    ticks: Option<usize>,
+    criteria: Criteria,
+    vehicle: Vehicle,
+    noise: NoiseConfig,
+    damage: DamageModel,
+    baseline: Option<String>,
+    json: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut options = Self::default();
+        let mut brain = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                "--json" => {
+                    options.json = true;
+                    continue;
+                }
+                _ if !arg.starts_with("--") => {
+                    brain = Some(arg);
+                    continue;
+                }
+                _ => (),
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for `{arg}`"))?;
+
+            match arg.as_str() {
+                "--scenario" => options.scenarios.push(value),
+                "--ticks" => options.ticks = Some(value.parse()?),
+                "--min-distance" => options.criteria.min_distance = value.parse()?,
+                "--baseline" => options.baseline = Some(value),
+                "--range-noise" => options.noise.range_sigma = value.parse()?,
+                "--ray-dropout" => options.noise.dropout = value.parse()?,
+                "--range-levels" => options.noise.range_levels = value.parse()?,
+                "--sensor-latency" => options.noise.latency = value.parse()?,
+                "--crash-speed" => options.damage.crash_speed = value.parse()?,
+                "--scrape-slowdown" => options.damage.scrape_slowdown = value.parse()?,
+                "--vehicle" => {
+                    options.vehicle = vehicle::from_name(&value)
+                        .ok_or_else(|| format!("unknown vehicle model `{value}`"))?
+                }
+                _ => return Err(format!("unknown option `{arg}`").into()),
+            }
+        }
+
+        options.brain = brain.ok_or("missing <BRAIN>")?;
+        Ok(options)
+    }
+}
+
+fn run(options: Options) -> Result<bool, Box<dyn Error>> {
+    let brain = NeuralNetwork::decode(&fs::read(&options.brain)?)?;
+
+    let mut cases = if options.scenarios.is_empty() {
+        benchmark::standard_suite()
+    } else {
+        options
+            .scenarios
+            .iter()
+            .map(|path| {
+                let scenario = Scenario::parse(&fs::read_to_string(path)?)?;
+                Ok(BenchmarkCase::new(scenario, 2_000, options.criteria))
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?
+    };
+    if let Some(ticks) = options.ticks {
+        for case in &mut cases {
+            case.ticks = ticks;
+        }
+    }
+
+    let report = benchmark::run(
+        &brain,
+        &cases,
+        &options.vehicle,
+        options.noise,
+        options.damage,
+    )?;
+    if options.json {
+        println!("{}", report.to_json());
+    } else {
+        println!("{report}");
+    }
+
+    match &options.baseline {
+        Some(path) => {
+            let baseline: Report = serde_json::from_str(&fs::read_to_string(path)?)?;
+            let regressions = report.regressions(&baseline);
+            for regression in &regressions {
+                eprintln!("regression: {regression}");
+            }
+            Ok(regressions.is_empty())
+        }
+        None => Ok(report.passed()),
+    }
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    match run(options) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("error: {err}");
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::{env, error::Error, fs, process, rc::Rc};
+
+use rand::{thread_rng, Rng};
+use self_driving_car::{
+    brain::Precision,
+    controls::ControlKind,
+    damage::DamageModel,
+    fitness::{self, Fitness},
+    network::{BrainConfig, NeuralNetwork},
+    noise::NoiseConfig,
+    scenario::{RoadGeometry, Scenario},
+    sensor,
+    traffic::{TrafficConfig, TrafficSpawner},
+    vehicle::{self, Vehicle},
+    world::{GenerationConfig, Interaction, World},
+};
+
+const USAGE: &str = "\
+Usage: train [OPTIONS]
+
+Options:
+    --generations <N>   number of generations to run (default: 10)
+    --ticks <N>         tick budget per generation (default: 3000)
+    --stuck-ticks <N>   window over which a car must make progress, or be eliminated
+                        (default: 200)
+    --min-progress <F>  distance along the road a car must make every window (default: 10)
+    --max-lag <F>       eliminate cars this far behind the best car (default: never)
+    --cars <N>          population size (default: the scenario's, 100)
+    --mutation <S>      mutation schedule: <amount>, linear:<start>:<end>:<generations>
+                        or exp:<start>:<decay>:<min> (default: 0.1)
+    --selection <S>     tournament, tournament:<size> or roulette (default: tournament:3)
+    --crossover <C>     uniform or single-point (default: uniform)
+    --crossover-rate <F>
+                        chance a child has two parents (default: 0.7)
+    --elitism <N>       best brains carried over unchanged (default: 1)
+    --activations <A,..>
+                        activation per level: step, sigmoid, tanh, relu or leaky-relu,
+                        the last one repeating for deeper levels; analog brains end in
+                        tanh unless given tanh or leaky-relu (default: step)
+    --control <MODE>    buttons or analog brain outputs (default: buttons)
+    --sensor <NAME>     front, wide, 360, aware or rear sensor layout (default: front)
+    --rays <N>          sensor ray count, one brain input each (default: 5)
+    --ray-length <L>    sensor ray length in pixels (default: 150)
+    --ray-spread <DEG>  angle between the outermost rays, 360 for all around (default: 90)
+    --ray-offset <DEG>  direction the rays fan out around, 180 facing backwards (default: 0)
+    --sensor-mount <RIGHT>:<FORWARD>
+                        where the rays start relative to the car's center (default: 0:0)
+    --sensor-inputs <I,..>
+                        extra brain inputs: hit-kind and closing-speed per ray, speed,
+                        heading and lane-offset, or none (default: none)
+    --range-noise <F>   standard deviation of the noise on sensor ranges, in ray lengths
+                        (default: 0)
+    --ray-dropout <P>   chance a sensor ray reads nothing on a tick (default: 0)
+    --range-levels <N>  distinct ranges a sensor ray reports, 0 for exact (default: 0)
+    --sensor-latency <N>
+                        ticks before the brain sees a sensor reading (default: 0)
+    --vehicle <NAME>    arcade or bicycle vehicle model (default: arcade)
+    --scenario <FILE>   road, traffic, starting point and population, as JSON or RON
+                        (default: the tutorial's)
+    --road <KIND>       straight or curvy, the curvy road drawn from the seed
+                        (default: the scenario's road)
+    --traffic <KIND>    endless traffic or the scenario's fixed traffic
+                        (default: fixed with --scenario, endless otherwise)
+    --traffic-density <F>
+                        endless traffic cars per 1000px of road (default: 8)
+    --traffic-lanes <P> random (one car per row) or gaps (one free lane per row) (default: random)
+    --traffic-speed <S> traffic top speed: <speed> or <min>:<max> (default: 2)
+    --traffic-driver <D>
+                        dummy (holds speed and lane) or agent (follows, brakes and
+                        changes lanes) endless traffic (default: dummy)
+    --crash-speed <F>   impacts at least this fast disable a car, slower ones only scrape;
+                        0 makes every contact a crash (default: 0)
+    --scrape-slowdown <F>
+                        share of its speed a car loses in a scrape (default: 0.5)
+    --interaction <I>   ghosts (AI cars drive through each other), collide (they sense and
+                        hit each other) or private-traffic (each drives in its own copy of
+                        the traffic) (default: ghosts)
+    --fitness <NAME>    distance, crash-penalty, overtaken, lane-keeping or comfort (default: distance)
+    --seed <N>          seed for every random decision, printed when omitted
+    --brain <FILE>      brain to start from, as exported from the web UI or written by this tool;
+                        it brings the sensor layout it was trained with
+    --output <FILE>     where to write the best brain of all generations so far, in the compact
+                        binary format if the name ends in .bin (default: best_brain.json)
+    --precision <P>     f64 or f32 parameters in binary brains (default: f64)
+    --help              print this message";
+
+#[derive(Debug)]
+struct Options {
+    generations: usize,
+    config: GenerationConfig,
+    cars: Option<usize>,
+    brain_config: BrainConfig,
+    vehicle: Vehicle,
+    noise: NoiseConfig,
+    damage: DamageModel,
+    interaction: Interaction,
+    scenario: Option<String>,
+    road: Option<RoadGeometry>,
+    traffic: Option<TrafficConfig>,
+    fitness: Rc<dyn Fitness>,
+    seed: Option<u64>,
+    brain: Option<String>,
+    output: String,
+    precision: Precision,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            generations: 10,
+            config: GenerationConfig::default(),
+            cars: None,
+            brain_config: BrainConfig::default(),
+            vehicle: Vehicle::default(),
+            noise: NoiseConfig::default(),
+            damage: DamageModel::default(),
+            interaction: Interaction::default(),
+            scenario: None,
+            road: None,
+            traffic: Some(TrafficConfig::default()),
+            fitness: Rc::new(fitness::Distance),
+            seed: None,
+            brain: None,
+            output: "best_brain.json".to_string(),
+            precision: Precision::default(),
+        }
+    }
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut options = Self::default();
+        let mut traffic_given = false;
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for `{arg}`"))?;
+
+            traffic_given |= arg.starts_with("--traffic");
+            match arg.as_str() {
+                "--generations" => options.generations = value.parse()?,
+                "--ticks" => options.config.tick_budget = value.parse()?,
+                "--stuck-ticks" => options.config.stuck_ticks = value.parse()?,
+                "--min-progress" => options.config.min_progress = value.parse()?,
+                "--max-lag" => options.config.max_lag = Some(value.parse()?),
+                "--cars" => options.cars = Some(value.parse()?),
+                "--mutation" => options.config.genetics.mutation = value.parse()?,
+                "--selection" => options.config.genetics.selection = value.parse()?,
+                "--crossover" => options.config.genetics.crossover = value.parse()?,
+                "--crossover-rate" => options.config.genetics.crossover_rate = value.parse()?,
+                "--elitism" => options.config.genetics.elitism = value.parse()?,
+                "--activations" => {
+                    options.brain_config.activations =
+                        value.split(',').map(str::parse).collect::<Result<_, _>>()?
+                }
+                "--control" => options.brain_config.control_mode = value.parse()?,
+                "--sensor" => {
+                    options.brain_config.sensor = sensor::from_name(&value)
+                        .ok_or_else(|| format!("unknown sensor layout `{value}`"))?
+                }
+                "--rays" => options.brain_config.sensor.ray_count = value.parse()?,
+                "--ray-length" => options.brain_config.sensor.ray_length = value.parse()?,
+                "--ray-spread" => {
+                    options.brain_config.sensor.ray_spread = value.parse::<f64>()?.to_radians()
+                }
+                "--ray-offset" => {
+                    options.brain_config.sensor.ray_offset = value.parse::<f64>()?.to_radians()
+                }
+                "--sensor-mount" => {
+                    let (right, forward) = value
+                        .split_once(':')
+                        .ok_or_else(|| format!("expected <RIGHT>:<FORWARD>, got `{value}`"))?;
+                    options.brain_config.sensor.mount = (right.parse()?, forward.parse()?);
+                }
+                "--sensor-inputs" => options.brain_config.sensor.modalities = value.parse()?,
+                "--range-noise" => options.noise.range_sigma = value.parse()?,
+                "--ray-dropout" => options.noise.dropout = value.parse()?,
+                "--range-levels" => options.noise.range_levels = value.parse()?,
+                "--sensor-latency" => options.noise.latency = value.parse()?,
+                "--vehicle" => {
+                    options.vehicle = vehicle::from_name(&value)
+                        .ok_or_else(|| format!("unknown vehicle model `{value}`"))?
+                }
+                "--scenario" => options.scenario = Some(value),
+                "--road" => {
+                    options.road = Some(match value.as_str() {
+                        "straight" => RoadGeometry::Straight,
+                        "curvy" => RoadGeometry::Procedural { seed: None },
+                        _ => return Err(format!("unknown road `{value}`").into()),
+                    })
+                }
+                "--traffic" => {
+                    options.traffic = match value.as_str() {
+                        "fixed" => None,
+                        "endless" => Some(options.traffic.unwrap_or_default()),
+                        _ => return Err(format!("unknown traffic `{value}`").into()),
+                    }
+                }
+                "--traffic-density" => {
+                    options.traffic.get_or_insert_with(Default::default).density = value.parse()?
+                }
+                "--traffic-lanes" => {
+                    options.traffic.get_or_insert_with(Default::default).lanes = value.parse()?
+                }
+                "--traffic-speed" => {
+                    options.traffic.get_or_insert_with(Default::default).speed = value.parse()?
+                }
+                "--traffic-driver" => {
+                    options.traffic.get_or_insert_with(Default::default).driver =
+                        match value.as_str() {
+                            "dummy" => ControlKind::Dummy,
+                            "agent" => ControlKind::Agent,
+                            _ => return Err(format!("unknown traffic driver `{value}`").into()),
+                        }
+                }
+                "--crash-speed" => options.damage.crash_speed = value.parse()?,
+                "--scrape-slowdown" => options.damage.scrape_slowdown = value.parse()?,
+                "--interaction" => options.interaction = value.parse()?,
+                "--fitness" => {
+                    options.fitness = fitness::from_name(&value)
+                        .ok_or_else(|| format!("unknown fitness `{value}`"))?
+                }
+                "--seed" => options.seed = Some(value.parse()?),
+                "--brain" => options.brain = Some(value),
+                "--output" => options.output = value,
+                "--precision" => options.precision = value.parse()?,
+                _ => return Err(format!("unknown option `{arg}`").into()),
+            }
+        }
+
+        // A scenario brings its own traffic.
+        if options.scenario.is_some() && !traffic_given {
+            options.traffic = None;
+        }
+
+        Ok(options)
+    }
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let best_brain = match &options.brain {
+        Some(path) => Some(NeuralNetwork::decode(&fs::read(path)?)?),
+        None => None,
+    };
+
+    let seed = options
+        .seed
+        .unwrap_or_else(|| thread_rng().gen::<u32>() as u64);
+    println!("seed: {seed}");
+
+    let mut scenario = match &options.scenario {
+        Some(path) => Scenario::parse(&fs::read_to_string(path)?)?,
+        None => Scenario::default(),
+    };
+    if let Some(geometry) = options.road {
+        scenario.road.geometry = geometry;
+    }
+
+    let cars = options.cars.unwrap_or(scenario.population);
+    let mut world = World::new(scenario.road.build(seed), Vec::new(), Vec::new(), seed);
+    world.scenario = scenario;
+    world.config = options.config;
+    world.fitness = options.fitness;
+    world.brain = options.brain_config;
+    world.vehicle = options.vehicle;
+    world.noise = options.noise;
+    world.damage = options.damage;
+    world.interaction = options.interaction;
+    world.spawner = options
+        .traffic
+        .map(|traffic| TrafficSpawner::new(traffic, seed));
+    world.populate(cars);
+
+    if let Some(brain) = &best_brain {
+        world.load_brain(brain, options.config.genetics.mutation.amount(0))?;
+    }
+
+    // Only a better brain replaces the one already written.
+    let mut best_fitness = f64::NEG_INFINITY;
+    while world.generation < options.generations {
+        world.step();
+
+        if !world.generation_over() {
+            continue;
+        }
+
+        let Some(best_car) = world.best_car() else {
+            return Err("population is empty".into());
+        };
+
+        println!(
+            "generation {}: best {} = {:.1}, y = {:.1} after {} ticks, damaged = {}, scrapes = {}",
+            world.generation + 1,
+            world.fitness.name(),
+            best_car.fitness,
+            best_car.y,
+            world.tick,
+            best_car.damaged,
+            best_car.stats.scrapes
+        );
+        let fitness = best_car.fitness;
+
+        let Some(best_brain) = world.next_generation() else {
+            continue;
+        };
+        if fitness > best_fitness {
+            best_fitness = fitness;
+            if options.output.ends_with(".bin") {
+                fs::write(&options.output, best_brain.to_bytes(options.precision))?;
+            } else {
+                fs::write(&options.output, best_brain.to_json())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(options) {
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,645 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::{
+    controls::ControlMode,
+    network::{Activation, Level, NeuralNetwork},
+    sensor::{Modalities, SensorConfig},
+};
+
+/// Version written by [`BrainFile::to_json`] and [`BrainFile::to_bytes`].
+/// Version 1 is the bare [`NeuralNetwork`], as saved before brain files had
+/// a header, version 2 kept the sensor in the header only, without its
+/// offset and mount, and version 3 had no sensor modalities.
+pub const FORMAT_VERSION: u32 = 4;
+
+/// Describes what a brain expects to be wired to, so a brain that does not
+/// fit is rejected on load instead of misbehaving while driving.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrainHeader {
+    pub version: u32,
+    /// What each input reads, in order.
+    pub inputs: Vec<String>,
+    /// What each output drives, in order.
+    pub outputs: Vec<String>,
+    /// Activation of each level.
+    pub activations: Vec<Activation>,
+    pub control_mode: ControlMode,
+    pub sensor: SensorConfig,
+}
+
+impl BrainHeader {
+    pub fn describe(network: &NeuralNetwork) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            inputs: network.sensor.input_names(),
+            outputs: network
+                .control_mode
+                .output_names()
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            activations: network
+                .levels
+                .iter()
+                .map(|level| level.activation)
+                .collect(),
+            control_mode: network.control_mode,
+            sensor: network.sensor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrainFile {
+    pub header: BrainHeader,
+    pub network: NeuralNetwork,
+}
+
+#[derive(Debug)]
+pub enum BrainError {
+    Json(serde_json::Error),
+    /// A truncated or corrupted binary brain.
+    Binary(String),
+    /// Written by a newer version of the simulator.
+    UnsupportedVersion(u32),
+    /// Well-formed, but the network does not fit its header or the cars.
+    Invalid(String),
+}
+
+impl fmt::Display for BrainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "invalid brain file: {err}"),
+            Self::Binary(reason) => write!(f, "invalid binary brain: {reason}"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "brain file version {version} is newer than the supported version {FORMAT_VERSION}"
+            ),
+            Self::Invalid(reason) => write!(f, "invalid brain: {reason}"),
+        }
+    }
+}
+
+impl Error for BrainError {}
+
+impl BrainFile {
+    pub fn new(network: NeuralNetwork) -> Self {
+        Self {
+            header: BrainHeader::describe(&network),
+            network,
+        }
+    }
+
+    /// Reads a brain file of any supported version, migrating older ones.
+    pub fn parse(text: &str) -> Result<Self, BrainError> {
+        let value: Value = serde_json::from_str(text).map_err(BrainError::Json)?;
+
+        let file = match value.get("header") {
+            Some(header) => {
+                let version = header
+                    .get("version")
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| BrainError::Invalid("the header has no version".to_string()))?;
+                if version > FORMAT_VERSION as u64 {
+                    return Err(BrainError::UnsupportedVersion(version as u32));
+                }
+                let mut file: Self = serde_json::from_value(value).map_err(BrainError::Json)?;
+                if version < 3 {
+                    file.network.sensor = file.header.sensor;
+                }
+                file.header.version = FORMAT_VERSION;
+                file
+            }
+            // Version 1 brains were only ever used with the default sensor.
+            None => Self::new(serde_json::from_value(value).map_err(BrainError::Json)?),
+        };
+
+        file.validate()?;
+        Ok(file)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Reads a brain file in any of its forms: JSON, binary or base64 binary.
+    pub fn decode(bytes: &[u8]) -> Result<Self, BrainError> {
+        if bytes.starts_with(MAGIC) {
+            return Self::from_bytes(bytes);
+        }
+
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| BrainError::Binary("neither JSON nor a binary brain".to_string()))?
+            .trim();
+        if text.starts_with('{') {
+            Self::parse(text)
+        } else {
+            Self::from_base64(text)
+        }
+    }
+
+    /// The header, then every parameter in [`NeuralNetwork::parameters`]
+    /// order, all little-endian. The levels' transient inputs and outputs are
+    /// left out and read back as zeros.
+    pub fn to_bytes(&self, precision: Precision) -> Vec<u8> {
+        let (header, network) = (&self.header, &self.network);
+        let mut bytes = MAGIC.to_vec();
+
+        bytes.extend((FORMAT_VERSION as u16).to_le_bytes());
+        bytes.push(precision.width());
+        bytes.push(match network.control_mode {
+            ControlMode::Buttons => 0,
+            ControlMode::Analog => 1,
+        });
+        bytes.extend((header.sensor.ray_count as u32).to_le_bytes());
+        bytes.extend((header.sensor.ray_length as u32).to_le_bytes());
+        bytes.extend(header.sensor.ray_spread.to_le_bytes());
+        bytes.extend(header.sensor.ray_offset.to_le_bytes());
+        bytes.extend(header.sensor.mount.0.to_le_bytes());
+        bytes.extend(header.sensor.mount.1.to_le_bytes());
+        bytes.push(header.sensor.modalities.to_bits());
+
+        bytes.extend((network.levels.len() as u32).to_le_bytes());
+        for level in &network.levels {
+            bytes.extend((level.inputs.len() as u32).to_le_bytes());
+            bytes.extend((level.outputs.len() as u32).to_le_bytes());
+            bytes.push(
+                Activation::ALL
+                    .iter()
+                    .position(|&activation| activation == level.activation)
+                    .unwrap() as u8,
+            );
+        }
+
+        for &parameter in network.parameters() {
+            match precision {
+                Precision::F32 => bytes.extend((parameter as f32).to_le_bytes()),
+                Precision::F64 => bytes.extend(parameter.to_le_bytes()),
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BrainError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(BrainError::Binary("not a brain file".to_string()));
+        }
+        let version = reader.u16()? as u32;
+        if version > FORMAT_VERSION {
+            return Err(BrainError::UnsupportedVersion(version));
+        }
+        let precision = match reader.u8()? {
+            4 => Precision::F32,
+            8 => Precision::F64,
+            width => return Err(BrainError::Binary(format!("unknown precision {width}"))),
+        };
+        let control_mode = match reader.u8()? {
+            0 => ControlMode::Buttons,
+            1 => ControlMode::Analog,
+            mode => return Err(BrainError::Binary(format!("unknown control mode {mode}"))),
+        };
+        let mut sensor = SensorConfig {
+            ray_count: reader.u32()? as usize,
+            ray_length: reader.u32()? as usize,
+            ray_spread: reader.f64()?,
+            ..Default::default()
+        };
+        if version >= 3 {
+            sensor.ray_offset = reader.f64()?;
+            sensor.mount = (reader.f64()?, reader.f64()?);
+        }
+        if version >= 4 {
+            sensor.modalities = Modalities::from_bits(reader.u8()?);
+        }
+
+        let level_count = reader.u32()? as usize;
+        let mut shapes = Vec::new();
+        for _ in 0..level_count {
+            let (inputs, outputs) = (reader.u32()? as usize, reader.u32()? as usize);
+            let activation = *Activation::ALL
+                .get(reader.u8()? as usize)
+                .ok_or_else(|| BrainError::Binary("unknown activation".to_string()))?;
+            shapes.push((inputs, outputs, activation));
+        }
+
+        // The levels must chain from the sensor's inputs, each with some inputs
+        // and outputs, or a level without outputs could ask for any number
+        // of inputs at no cost in parameters.
+        let mut expected = sensor.input_count();
+        for (i, &(inputs, outputs, _)) in shapes.iter().enumerate() {
+            if inputs == 0 || outputs == 0 {
+                return Err(BrainError::Invalid(format!(
+                    "level {i} has {inputs} inputs and {outputs} outputs"
+                )));
+            }
+            if inputs != expected {
+                return Err(BrainError::Invalid(format!(
+                    "level {i} reads {inputs} inputs, but gets {expected}"
+                )));
+            }
+            expected = outputs;
+        }
+
+        // Checked up front so a corrupted count cannot allocate unbounded memory,
+        // nor overflow the size on 32-bit targets.
+        let too_large = || BrainError::Binary("the network is too large".to_string());
+        let parameter_count = shapes
+            .iter()
+            .try_fold(0usize, |count, &(inputs, outputs, _)| {
+                inputs
+                    .checked_mul(outputs)
+                    .and_then(|weights| weights.checked_add(outputs))
+                    .and_then(|parameters| count.checked_add(parameters))
+            })
+            .ok_or_else(too_large)?;
+        let size = parameter_count
+            .checked_mul(precision.width() as usize)
+            .ok_or_else(too_large)?;
+        if reader.bytes.len() != size {
+            return Err(BrainError::Binary(format!(
+                "expected {parameter_count} parameters in {size} bytes, found {} bytes",
+                reader.bytes.len()
+            )));
+        }
+
+        let mut network = NeuralNetwork {
+            levels: shapes
+                .into_iter()
+                .map(|(inputs, outputs, activation)| Level {
+                    inputs: vec![0.0; inputs],
+                    outputs: vec![0.0; outputs],
+                    biases: vec![0.0; outputs],
+                    weights: vec![vec![0.0; outputs]; inputs],
+                    activation,
+                })
+                .collect(),
+            control_mode,
+            sensor,
+        };
+        for parameter in network.parameters_mut() {
+            *parameter = match precision {
+                Precision::F32 => reader.f32()? as f64,
+                Precision::F64 => reader.f64()?,
+            };
+        }
+
+        let file = Self::new(network);
+        file.validate()?;
+        Ok(file)
+    }
+
+    /// [`BrainFile::to_bytes`] as text, for storage that only holds strings.
+    pub fn to_base64(&self, precision: Precision) -> String {
+        STANDARD.encode(self.to_bytes(precision))
+    }
+
+    pub fn from_base64(text: &str) -> Result<Self, BrainError> {
+        let bytes = STANDARD
+            .decode(text.trim())
+            .map_err(|err| BrainError::Binary(err.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn validate(&self) -> Result<(), BrainError> {
+        let invalid = |reason: String| Err(BrainError::Invalid(reason));
+        let (header, network) = (&self.header, &self.network);
+
+        validate_network(network)?;
+
+        if header.sensor != network.sensor {
+            return invalid("the header's sensor differs from the network's".to_string());
+        }
+        if header.inputs != network.sensor.input_names() {
+            return invalid(format!(
+                "the header lists inputs {:?}, but the sensor provides {:?}",
+                header.inputs,
+                network.sensor.input_names()
+            ));
+        }
+        if header.control_mode != network.control_mode
+            || header.outputs != network.control_mode.output_names()
+        {
+            return invalid(format!(
+                "the header's outputs {:?} do not match {:?} control",
+                header.outputs,
+                network.control_mode.output_names()
+            ));
+        }
+        if header.activations.len() != network.levels.len()
+            || header
+                .activations
+                .iter()
+                .zip(&network.levels)
+                .any(|(&activation, level)| activation != level.activation)
+        {
+            return invalid("the header's activations do not match the levels".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the levels chain up, read the sensor's inputs and drive
+/// the control mode's outputs, and that every parameter is a number.
+pub fn validate_network(network: &NeuralNetwork) -> Result<(), BrainError> {
+    let invalid = |reason: String| Err(BrainError::Invalid(reason));
+
+    if network.levels.is_empty() {
+        return invalid("the network has no levels".to_string());
+    }
+
+    for (i, level) in network.levels.iter().enumerate() {
+        if level.weights.len() != level.inputs.len()
+            || level.biases.len() != level.outputs.len()
+            || level
+                .weights
+                .iter()
+                .any(|row| row.len() != level.outputs.len())
+        {
+            return invalid(format!(
+                "level {i} has {} inputs and {} outputs, but {} biases and {} weight rows",
+                level.inputs.len(),
+                level.outputs.len(),
+                level.biases.len(),
+                level.weights.len()
+            ));
+        }
+        if let Some(next) = network.levels.get(i + 1) {
+            if next.inputs.len() != level.outputs.len() {
+                return invalid(format!(
+                    "level {} reads {} inputs, but level {i} has {} outputs",
+                    i + 1,
+                    next.inputs.len(),
+                    level.outputs.len()
+                ));
+            }
+        }
+    }
+
+    let inputs = network.shape()[0];
+    if inputs != network.sensor.input_count() {
+        return invalid(format!(
+            "the network reads {inputs} inputs, but its sensor provides {}",
+            network.sensor.input_count()
+        ));
+    }
+
+    let outputs = *network.shape().last().unwrap();
+    if outputs != network.control_mode.output_count() {
+        return invalid(format!(
+            "the network has {outputs} outputs, but {:?} control needs {}",
+            network.control_mode,
+            network.control_mode.output_count()
+        ));
+    }
+    if network.parameters().any(|parameter| !parameter.is_finite()) {
+        return invalid("the network has parameters that are not finite".to_string());
+    }
+    Ok(())
+}
+
+const MAGIC: &[u8] = b"SDCB";
+
+/// How parameters are stored in binary brains. `F64` round-trips exactly;
+/// `F32` halves the size and rounds every parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    #[default]
+    F64,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32" => Ok(Self::F32),
+            "f64" => Ok(Self::F64),
+            _ => Err(format!("unknown precision `{s}`")),
+        }
+    }
+}
+
+impl Precision {
+    fn width(self) -> u8 {
+        match self {
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], BrainError> {
+        if self.bytes.len() < count {
+            return Err(BrainError::Binary("the file ends early".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, BrainError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BrainError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, BrainError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, BrainError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, BrainError> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Stores a network field as base64 binary, reading back either that or the
+/// JSON form saved before. For use with `#[serde(with = "...")]`.
+pub mod compact {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        network: &NeuralNetwork,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        BrainFile::new(network.clone())
+            .to_base64(Precision::F64)
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NeuralNetwork, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Compact(String),
+            Json(NeuralNetwork),
+        }
+
+        match Stored::deserialize(deserializer)? {
+            Stored::Compact(text) => BrainFile::from_base64(&text)
+                .map(|file| file.network)
+                .map_err(serde::de::Error::custom),
+            Stored::Json(network) => Ok(network),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BrainError, BrainFile, Precision, FORMAT_VERSION, MAGIC};
+    use crate::sensor::SensorConfig;
+
+    #[test]
+    fn test_version_1_brains_are_migrated() {
+        let json = r#"{"levels":[{"inputs":[0.0,0.0,0.0,0.0,0.0],"outputs":[0.0,0.0],"biases":[0.5,0.5],"weights":[[1.0,0.0],[1.0,0.0],[1.0,0.0],[1.0,0.0],[1.0,0.0]]}],"control_mode":"analog"}"#;
+        let file = BrainFile::parse(json).unwrap();
+
+        assert_eq!(file.header.version, FORMAT_VERSION);
+        assert_eq!(file.header.sensor, SensorConfig::default());
+        assert_eq!(file.header.outputs, ["throttle", "steering"]);
+        assert_eq!(BrainFile::parse(&file.to_json()).unwrap(), file);
+
+        // Version 2 kept the sensor in the header only.
+        let mut value: serde_json::Value = serde_json::from_str(&file.to_json()).unwrap();
+        value["header"]["version"] = 2.into();
+        value["header"]["sensor"]["ray_length"] = 200.into();
+        value["network"].as_object_mut().unwrap().remove("sensor");
+        let file = BrainFile::parse(&value.to_string()).unwrap();
+        assert_eq!(file.network.sensor.ray_length, 200);
+        assert_eq!(file.header.version, FORMAT_VERSION);
+    }
+
+    #[test]
+    fn test_broken_brains_are_rejected() {
+        let file = BrainFile::parse(include_str!("../brains/reference.json")).unwrap();
+
+        let mut newer = file.clone();
+        newer.header.version = FORMAT_VERSION + 1;
+        assert!(matches!(
+            BrainFile::parse(&newer.to_json()),
+            Err(BrainError::UnsupportedVersion(_))
+        ));
+
+        let mut more_rays = file.clone();
+        more_rays.header.sensor.ray_count = 7;
+        assert!(matches!(
+            BrainFile::parse(&more_rays.to_json()),
+            Err(BrainError::Invalid(_))
+        ));
+
+        let mut truncated = file;
+        truncated.network.levels[0].weights.pop();
+        let err = BrainFile::parse(&truncated.to_json()).unwrap_err();
+        assert!(err.to_string().contains("level 0"));
+
+        assert!(matches!(
+            BrainFile::parse("{\"levels\": 3}"),
+            Err(BrainError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_binary_round_trips_with_json() {
+        let file = BrainFile::parse(include_str!("../brains/reference.json")).unwrap();
+        let json = file.to_json();
+
+        let bytes = file.to_bytes(Precision::F64);
+        assert!(bytes.len() * 3 < json.len());
+        let decoded = BrainFile::decode(&bytes).unwrap();
+        assert!(decoded.network.parameters().eq(file.network.parameters()));
+        assert_eq!(decoded.header, file.header);
+        // Only the transient inputs and outputs are lost.
+        assert_eq!(
+            BrainFile::parse(&decoded.to_json())
+                .unwrap()
+                .to_bytes(Precision::F64),
+            bytes
+        );
+
+        let base64 = file.to_base64(Precision::F32);
+        let decoded = BrainFile::decode(base64.as_bytes()).unwrap();
+        assert!(decoded
+            .network
+            .parameters()
+            .zip(file.network.parameters())
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        assert!(matches!(
+            BrainFile::decode(&bytes[..bytes.len() - 1]),
+            Err(BrainError::Binary(_))
+        ));
+    }
+
+    /// The reference brain's binary header, up to its level count.
+    fn binary_header() -> Vec<u8> {
+        let file = BrainFile::parse(include_str!("../brains/reference.json")).unwrap();
+        let bytes = file.to_bytes(Precision::F64);
+        let levels = file.network.levels.len();
+        let header = bytes.len() - file.network.parameters().count() * 8 - levels * 9 - 4;
+        bytes[..header].to_vec()
+    }
+
+    #[test]
+    fn test_oversized_binary_header_is_rejected() {
+        // A sensor with u32::MAX rays, read by two levels of u32::MAX by
+        // u32::MAX, overflows any parameter count.
+        let mut oversized = binary_header();
+        let rays = MAGIC.len() + 4;
+        oversized[rays..rays + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        oversized.extend(2u32.to_le_bytes());
+        for _ in 0..2 {
+            oversized.extend(u32::MAX.to_le_bytes());
+            oversized.extend(u32::MAX.to_le_bytes());
+            oversized.push(0);
+        }
+        let err = BrainFile::from_bytes(&oversized).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_binary_levels_must_chain_from_the_sensor() {
+        let level = |inputs: u32, outputs: u32| {
+            let mut bytes = binary_header();
+            bytes.extend(1u32.to_le_bytes());
+            bytes.extend(inputs.to_le_bytes());
+            bytes.extend(outputs.to_le_bytes());
+            bytes.push(0);
+            bytes
+        };
+
+        // Without outputs, a huge level would have no parameters to check.
+        let no_outputs = level(u32::MAX, 0);
+        assert_eq!(no_outputs.len(), 62);
+        assert!(matches!(
+            BrainFile::from_bytes(&no_outputs),
+            Err(BrainError::Invalid(_))
+        ));
+        assert!(matches!(
+            BrainFile::from_bytes(&level(0, 4)),
+            Err(BrainError::Invalid(_))
+        ));
+        assert!(matches!(
+            BrainFile::from_bytes(&level(6, 4)),
+            Err(BrainError::Invalid(_))
+        ));
+    }
+}
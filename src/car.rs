@@ -1,15 +1,16 @@
 use std::f64::consts::PI;
 
-use gloo::utils::document;
-use js_sys::Function;
-use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
-use web_sys::{CanvasRenderingContext2d, Element, HtmlCanvasElement, HtmlImageElement};
-
 use crate::{
+    agent::Agent,
     controls::{ControlKind, Controls, ControlsPtr},
+    damage::{self, Collision, DamageModel, Severity},
+    fitness::DrivingStats,
     network::NeuralNetwork,
+    noise::SensorNoise,
     sensor::Sensor,
-    utils::{polys_intersect, Coord},
+    spatial::{bounds, Obstacle, Obstacles},
+    utils::{poly_contacts, poly_edges, Coord},
+    vehicle::Vehicle,
 };
 
 pub type CarPtr = Car;
@@ -26,17 +27,31 @@ pub struct Car {
     pub max_speed: f64,
     pub friction: f64,
     pub angle: f64,
+    pub vehicle: Vehicle,
     pub damaged: bool,
+    /// Taken out of the generation for making too little progress.
+    pub eliminated: bool,
+    /// How hard the car can hit something before it is damaged.
+    pub damage: DamageModel,
+    /// What the car touched in the last tick, if anything.
+    pub collision: Option<Collision>,
     pub use_brain: bool,
     pub sensor: Option<Sensor>,
+    /// Degrades the sensor's inputs before the brain sees them.
+    pub noise: Option<SensorNoise>,
     pub brain: Option<NeuralNetwork>,
     pub controls: ControlsPtr,
-    pub img: Option<HtmlImageElement>,
-    pub mask: Option<Element>,
+    pub agent: Option<Agent>,
+    pub color: String,
     pub polygon: Vec<Coord>,
+    pub stats: DrivingStats,
+    pub fitness: f64,
+    /// Index into the population, for AI cars.
+    pub member: Option<usize>,
 }
 
 impl Car {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         x: f64,
         y: f64,
@@ -45,6 +60,7 @@ impl Car {
         control_kind: ControlKind,
         max_speed: Option<f64>,
         color: Option<&str>,
+        brain: Option<NeuralNetwork>,
     ) -> Car {
         let color = color.unwrap_or("blue");
         let max_speed = max_speed.unwrap_or(3.0);
@@ -59,183 +75,70 @@ impl Car {
             friction: 0.05,
             angle: 0.0,
             use_brain: control_kind == ControlKind::AI,
+            color: color.to_string(),
+            stats: DrivingStats::new(-y),
             ..Default::default()
         };
 
-        if !matches!(control_kind, ControlKind::Dummy) {
-            this.sensor = Some(Sensor::new());
-            if let Some(sensor) = &mut this.sensor {
-                this.brain = Some(NeuralNetwork::new(vec![sensor.ray_count, 6, 4]))
+        match control_kind {
+            ControlKind::Keys | ControlKind::AI => {
+                let config = brain.as_ref().map(|brain| brain.sensor);
+                this.sensor = Some(Sensor::new(config.unwrap_or_default()));
+                this.brain = brain;
             }
+            ControlKind::Agent => this.agent = Some(Agent::default()),
+            ControlKind::Dummy => (),
         }
 
         this.controls = Controls::new(control_kind);
 
-        this.img = HtmlImageElement::new().ok();
-        this.img.as_mut().unwrap().set_src("car.png");
-
-        this.mask = document().create_element("canvas").ok();
-        let mask_elmt = {
-            let elmt = this.mask.as_ref().unwrap();
-
-            elmt.dyn_ref::<HtmlCanvasElement>().unwrap()
-        };
-
-        mask_elmt.set_width(width as u32);
-        mask_elmt.set_height(height as u32);
-
-        let mask_ctx = mask_elmt
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<CanvasRenderingContext2d>()
-            .unwrap();
-
-        let onload = {
-            let color = color.to_string();
-            let img = this.img.clone().unwrap();
-            let width = this.width;
-            let height = this.height;
-
-            Closure::<dyn Fn()>::new(move || {
-                mask_ctx.set_fill_style(&JsValue::from_str(&color));
-                mask_ctx.rect(0.0, 0.0, width, height);
-                mask_ctx.fill();
-
-                mask_ctx
-                    .set_global_composite_operation("destination-atop")
-                    .unwrap();
-                mask_ctx
-                    .draw_image_with_html_image_element_and_dw_and_dh(&img, 0.0, 0.0, width, height)
-                    .unwrap();
-            })
-            .into_js_value()
-            .dyn_into::<Function>()
-            .unwrap()
-        };
-
-        if let Some(img) = &mut this.img {
-            img.set_onload(Some(&onload))
-        }
-
         this
     }
 
-    pub fn update(&mut self, road_borders: &[Vec<Coord>], traffic: &[CarPtr]) {
+    pub fn update(&mut self, obstacles: &Obstacles) {
         if !self.damaged {
+            let (x, y, angle) = (self.x, self.y, self.angle);
             self.r#move();
             self.polygon = self.create_polygon();
-            self.damaged = self.assess_damage(road_borders, traffic)
+
+            self.collision = self.assess_damage(obstacles);
+            if let Some(collision) = &self.collision {
+                match self.damage.severity(collision) {
+                    Severity::Crash => self.damaged = true,
+                    Severity::Scrape => {
+                        // Back out of the contact, turn included, slowed down.
+                        (self.x, self.y, self.angle) = (x, y, angle);
+                        self.polygon = self.create_polygon();
+                        self.speed *= 1.0 - self.damage.scrape_slowdown;
+                    }
+                }
+            }
         }
 
         let car = self.clone();
         if let Some(sensor) = &mut self.sensor {
-            sensor.update(&car, road_borders, traffic);
-            let offsets = sensor
-                .readings
-                .iter()
-                .map(|s| match s {
-                    Some(s) => 1.0 - s.offset,
-                    None => 0.0,
-                })
-                .collect::<Vec<f64>>();
+            sensor.update(&car, obstacles);
 
             if let Some(brain) = &mut self.brain {
-                let outputs = brain.feed_forward(offsets);
+                let inputs = match &mut self.noise {
+                    Some(noise) => noise.apply(sensor),
+                    None => sensor.inputs.clone(),
+                };
+                let outputs = brain.feed_forward(inputs);
 
                 if self.use_brain {
-                    self.controls.borrow_mut().forward = outputs[0] == 1.0;
-                    self.controls.borrow_mut().left = outputs[1] == 1.0;
-                    self.controls.borrow_mut().right = outputs[2] == 1.0;
-                    self.controls.borrow_mut().reverse = outputs[3] == 1.0;
+                    brain
+                        .control_mode
+                        .apply(&outputs, &mut self.controls.borrow_mut());
                 }
             }
         }
     }
 
-    pub fn draw(&mut self, ctx: &CanvasRenderingContext2d, draw_sensor: Option<bool>) {
-        let draw_sensor = draw_sensor.unwrap_or_default();
-
-        if let Some(sensor) = &self.sensor {
-            if draw_sensor {
-                sensor.draw(ctx);
-            }
-        }
-
-        ctx.save();
-        ctx.translate(self.x, self.y).unwrap();
-        ctx.rotate(-self.angle).unwrap();
-        if !self.damaged {
-            let mask = self
-                .mask
-                .as_ref()
-                .unwrap()
-                .dyn_ref::<HtmlCanvasElement>()
-                .unwrap();
-            ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
-                mask,
-                -self.width / 2.0,
-                -self.height / 2.0,
-                self.width,
-                self.height,
-            )
-            .unwrap();
-            ctx.set_global_composite_operation("multiply").unwrap();
-        }
-        ctx.draw_image_with_html_image_element_and_dw_and_dh(
-            &self.img.clone().unwrap(),
-            -self.width / 2.0,
-            -self.height / 2.0,
-            self.width,
-            self.height,
-        )
-        .unwrap();
-
-        ctx.restore();
-    }
-
     fn r#move(&mut self) {
-        if self.controls.borrow_mut().forward {
-            self.speed += self.acceleration;
-        }
-
-        if self.controls.borrow_mut().reverse {
-            self.speed -= self.acceleration;
-        }
-
-        if self.speed > self.max_speed {
-            self.speed = self.max_speed;
-        }
-
-        if self.speed < -self.max_speed / 2.0 {
-            self.speed = -self.max_speed / 2.0;
-        }
-
-        if self.speed > 0.0 {
-            self.speed -= self.friction;
-        }
-
-        if self.speed < 0.0 {
-            self.speed += self.friction;
-        }
-
-        if self.speed.abs() < self.friction {
-            self.speed = 0.0;
-        }
-
-        if self.speed != 0.0 {
-            let flip = if self.speed > 0.0 { 1.0 } else { -1.0 };
-
-            if self.controls.borrow_mut().left {
-                self.angle += 0.03 * flip;
-            }
-            if self.controls.borrow_mut().right {
-                self.angle -= 0.03 * flip;
-            }
-        }
-
-        self.x -= self.angle.sin() * self.speed;
-        self.y -= self.angle.cos() * self.speed;
+        let controls = *self.controls.borrow();
+        let vehicle = self.vehicle.clone();
+        vehicle.step(self, &controls);
     }
 
     fn create_polygon(&self) -> Vec<Coord> {
@@ -262,19 +165,128 @@ impl Car {
         points
     }
 
-    fn assess_damage(&self, road_borders: &[Vec<Coord>], traffic: &[CarPtr]) -> bool {
-        for road_border in road_borders {
-            if polys_intersect(&self.polygon, road_border) {
-                return true;
+    pub fn velocity(&self) -> Coord {
+        Coord {
+            x: -self.angle.sin() * self.speed,
+            y: -self.angle.cos() * self.speed,
+        }
+    }
+
+    /// Everything the car touches where it stands, if anything.
+    fn assess_damage(&self, obstacles: &Obstacles) -> Option<Collision> {
+        let (min, max) = bounds(&self.polygon)?;
+        let own = self.velocity();
+        let mut collision = Collision::default();
+
+        for obstacle in obstacles.near(min, max) {
+            let (contacts, velocity, with_car) = match obstacle {
+                // Borders stand still.
+                Obstacle::Segment(a, b) => (
+                    poly_contacts(&self.polygon, [(a, b)]),
+                    Coord { x: 0.0, y: 0.0 },
+                    false,
+                ),
+                Obstacle::Car(i) => {
+                    let body = &obstacles.bodies[i];
+                    (
+                        poly_contacts(&self.polygon, poly_edges(&body.polygon)),
+                        body.velocity(),
+                        true,
+                    )
+                }
+            };
+            collision.with_car |= with_car && !contacts.is_empty();
+
+            let relative = Coord {
+                x: own.x - velocity.x,
+                y: own.y - velocity.y,
+            };
+            for (contact, edge) in contacts {
+                collision.contacts.push(contact);
+                collision.impact_speed = collision
+                    .impact_speed
+                    .max(damage::impact_speed(relative, edge));
             }
         }
 
-        for t in traffic {
-            if polys_intersect(&self.polygon, &t.polygon) {
-                return true;
+        (!collision.contacts.is_empty()).then_some(collision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Car;
+    use crate::{
+        controls::{ControlKind, Controls},
+        damage::DamageModel,
+        spatial::Obstacles,
+        utils::{poly_contacts, Coord},
+    };
+
+    fn drive(controls: Controls) -> Car {
+        let mut car = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        *car.controls.borrow_mut() = controls;
+        for _ in 0..5 {
+            car.update(&Obstacles::new(&[], &[]));
+        }
+        car
+    }
+
+    #[test]
+    fn test_analog_controls_are_proportional() {
+        let full = drive(Controls {
+            throttle: 1.0,
+            steering: 1.0,
+            ..Default::default()
+        });
+        let half = drive(Controls {
+            throttle: 0.5,
+            steering: 0.5,
+            ..Default::default()
+        });
+
+        assert!(full.speed > half.speed && half.speed > 0.0);
+        assert!(full.angle > half.angle && half.angle > 0.0);
+
+        let braking = drive(Controls {
+            brake: 1.0,
+            ..Default::default()
+        });
+        assert!(braking.speed < 0.0);
+    }
+
+    #[test]
+    fn test_scraping_car_gets_free() {
+        let wall = vec![vec![
+            Coord { x: -20.0, y: 500.0 },
+            Coord {
+                x: -20.0,
+                y: -5_000.0,
+            },
+        ]];
+        let obstacles = Obstacles::new(&wall, &[]);
+        let mut car = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        car.damage = DamageModel {
+            crash_speed: 10.0,
+            ..Default::default()
+        };
+        car.use_brain = false;
+        car.controls.borrow_mut().throttle = 1.0;
+
+        // Steer into the wall, then away from it.
+        let mut scrapes = 0;
+        for steering in [1.0, -1.0] {
+            car.controls.borrow_mut().steering = steering;
+            for _ in 0..20 {
+                car.update(&obstacles);
+                scrapes += usize::from(car.collision.is_some());
+                // Never left inside the wall.
+                assert!(poly_contacts(&car.polygon, [(wall[0][0], wall[0][1])]).is_empty());
             }
         }
 
-        false
+        assert!(!car.damaged && car.collision.is_none());
+        assert!(scrapes > 0 && scrapes < 20);
+        assert!(car.y < -30.0);
     }
 }
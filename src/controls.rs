@@ -1,27 +1,92 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, str::FromStr};
 
 use gloo::utils::document;
 use js_sys::Function;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::{prelude::Closure, JsCast};
 use web_sys::KeyboardEvent;
 
 pub type ControlsPtr = Rc<RefCell<Controls>>;
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ControlKind {
     Keys,
     Dummy,
     AI,
+    /// Traffic driven by an [`Agent`](crate::agent::Agent).
+    Agent,
 }
 
+/// How a brain's outputs drive the [`Controls`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlMode {
+    /// Four outputs pressing forward, left, right and reverse when above 0.5.
+    #[default]
+    Buttons,
+    /// Two outputs in [-1, 1]: throttle (negative reverses) and steering
+    /// (positive turns left).
+    Analog,
+}
+
+impl ControlMode {
+    pub fn output_count(self) -> usize {
+        self.output_names().len()
+    }
+
+    /// What each output of a brain in this mode means, in order.
+    pub fn output_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Buttons => &["forward", "left", "right", "reverse"],
+            Self::Analog => &["throttle", "steering"],
+        }
+    }
+
+    /// Sets the controls from a brain's outputs.
+    pub fn apply(self, outputs: &[f64], controls: &mut Controls) {
+        let pressed = |output: f64| if output > 0.5 { 1.0 } else { 0.0 };
+
+        match self {
+            Self::Buttons => {
+                controls.throttle = pressed(outputs[0]);
+                controls.steering = pressed(outputs[1]) - pressed(outputs[2]);
+                controls.brake = pressed(outputs[3]);
+            }
+            Self::Analog => {
+                let throttle = outputs[0].clamp(-1.0, 1.0);
+                controls.throttle = throttle.max(0.0);
+                controls.brake = (-throttle).max(0.0);
+                controls.steering = outputs[1].clamp(-1.0, 1.0);
+            }
+        }
+    }
+}
+
+impl FromStr for ControlMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buttons" => Ok(Self::Buttons),
+            "analog" => Ok(Self::Analog),
+            _ => Err(format!("unknown control mode `{s}`")),
+        }
+    }
+}
+
+/// How hard each control is applied. Keyboard and button brains only ever
+/// use full-scale values; analog brains can drive smoothly.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Controls {
-    pub forward: bool,
-    pub left: bool,
-    pub right: bool,
-    pub reverse: bool,
+    /// Forward acceleration, in [0, 1].
+    pub throttle: f64,
+    /// Deceleration, and reverse once stopped, in [0, 1].
+    pub brake: f64,
+    /// Turn rate, in [-1, 1]; positive turns left.
+    pub steering: f64,
 }
 
 impl Controls {
@@ -32,8 +97,8 @@ impl Controls {
 
         match kind {
             ControlKind::Keys => Self::add_keyboard_listeners(&controls_ptr),
-            ControlKind::Dummy => controls_ptr.borrow_mut().forward = true,
-            ControlKind::AI => (),
+            ControlKind::Dummy => controls_ptr.borrow_mut().throttle = 1.0,
+            ControlKind::AI | ControlKind::Agent => (),
         }
 
         controls_ptr
@@ -45,10 +110,10 @@ impl Controls {
             Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
                 if let Ok(mut this) = this.try_borrow_mut() {
                     match event.key().as_str() {
-                        "ArrowLeft" => this.left = true,
-                        "ArrowRight" => this.right = true,
-                        "ArrowUp" => this.forward = true,
-                        "ArrowDown" => this.reverse = true,
+                        "ArrowLeft" => this.steering = 1.0,
+                        "ArrowRight" => this.steering = -1.0,
+                        "ArrowUp" => this.throttle = 1.0,
+                        "ArrowDown" => this.brake = 1.0,
                         _ => (),
                     }
                 }
@@ -67,10 +132,10 @@ impl Controls {
             Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
                 if let Ok(mut this) = this.try_borrow_mut() {
                     match event.key().as_str() {
-                        "ArrowLeft" => this.left = false,
-                        "ArrowRight" => this.right = false,
-                        "ArrowUp" => this.forward = false,
-                        "ArrowDown" => this.reverse = false,
+                        "ArrowLeft" if this.steering > 0.0 => this.steering = 0.0,
+                        "ArrowRight" if this.steering < 0.0 => this.steering = 0.0,
+                        "ArrowUp" => this.throttle = 0.0,
+                        "ArrowDown" => this.brake = 0.0,
                         _ => (),
                     }
                 }
@@ -0,0 +1,113 @@
+use crate::utils::Coord;
+
+/// What a car touched during a tick, and how hard.
+#[derive(Debug, Clone, Default)]
+pub struct Collision {
+    /// Where the car's outline crossed something.
+    pub contacts: Vec<Coord>,
+    /// How fast the car closed in on what it touched, across the surface it
+    /// touched: grazing a wall is slow, driving into it head-on is the car's
+    /// full speed.
+    pub impact_speed: f64,
+    /// Whether the car touched another car rather than only road borders.
+    pub with_car: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The car is pushed back and slowed down, but keeps driving.
+    Scrape,
+    /// The car is disabled for the rest of the generation.
+    Crash,
+}
+
+/// How hard a car can hit something before it is disabled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageModel {
+    /// Impacts at least this fast are crashes. At 0, the default, every
+    /// contact is, as in the tutorial.
+    pub crash_speed: f64,
+    /// Share of its speed a car loses in a scrape.
+    pub scrape_slowdown: f64,
+}
+
+impl Default for DamageModel {
+    fn default() -> Self {
+        Self {
+            crash_speed: 0.0,
+            scrape_slowdown: 0.5,
+        }
+    }
+}
+
+impl DamageModel {
+    pub fn severity(&self, collision: &Collision) -> Severity {
+        if collision.impact_speed >= self.crash_speed {
+            Severity::Crash
+        } else {
+            Severity::Scrape
+        }
+    }
+}
+
+/// How fast something moving at `velocity` relative to the segment `a`-`b`
+/// closes in on it, across the segment.
+pub fn impact_speed(velocity: Coord, (a, b): (Coord, Coord)) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return velocity.x.hypot(velocity.y);
+    }
+    ((velocity.x * dy - velocity.y * dx) / length).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DamageModel;
+    use crate::{car::Car, controls::ControlKind, spatial::Obstacles, utils::Coord};
+
+    /// A car at full speed, `angle` radians left of a wall straight ahead.
+    fn drive_into_wall(angle: f64) -> Car {
+        let wall = vec![vec![
+            Coord {
+                x: -500.0,
+                y: -30.0,
+            },
+            Coord { x: 500.0, y: -30.0 },
+        ]];
+        let mut car = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        car.damage = DamageModel {
+            crash_speed: 1.0,
+            ..Default::default()
+        };
+        car.angle = angle;
+        car.speed = car.max_speed;
+        car.controls.borrow_mut().throttle = 1.0;
+        for _ in 0..100 {
+            car.update(&Obstacles::new(&wall, &[]));
+            if car.collision.is_some() {
+                break;
+            }
+        }
+        car
+    }
+
+    #[test]
+    fn test_scrapes_slow_down_and_crashes_disable() {
+        let head_on = drive_into_wall(0.0);
+        let collision = head_on.collision.as_ref().unwrap();
+        assert!(head_on.damaged && !collision.with_car);
+        assert!((collision.impact_speed - head_on.speed).abs() < 1e-9);
+        assert!(collision
+            .contacts
+            .iter()
+            .all(|contact| (contact.y + 30.0).abs() < 1e-9));
+
+        // Almost parallel to the wall.
+        let glancing = drive_into_wall(1.5);
+        let collision = glancing.collision.as_ref().unwrap();
+        assert!(!glancing.damaged);
+        assert!(collision.impact_speed < 1.0);
+        assert!(glancing.speed < glancing.max_speed * 0.6);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{f64::consts::PI, fmt::Debug, rc::Rc};
+
+use crate::{car::Car, road::Road, utils::Coord, world::World};
+
+/// What a car has done so far in the current generation, recorded every tick
+/// so fitness functions can look past the car's final position.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DrivingStats {
+    /// Station along the road the car started from.
+    pub start_station: f64,
+    /// Station the car was at when last recorded.
+    pub station: f64,
+    /// Distance to the right of the centerline when last recorded.
+    pub lateral: f64,
+    /// Angle between the car and the road when last recorded, in radians;
+    /// positive when the car points left of the road.
+    pub heading: f64,
+    /// Signed distance to the nearest lane center when last recorded, in
+    /// lane widths; positive to the right.
+    pub lane_deviation: f64,
+    pub ticks: usize,
+    pub damaged_at: Option<usize>,
+    /// Ticks the car spent scraping along something without crashing.
+    pub scrapes: usize,
+    /// Fastest impact so far, the crash included.
+    pub hardest_impact: f64,
+    /// Sum over all ticks of the distance to the nearest lane center, in lane widths.
+    pub lane_offset: f64,
+    /// Number of times the car started straddling a lane marking.
+    pub lane_violations: usize,
+    /// Sum over all ticks of the change in longitudinal and lateral acceleration.
+    pub jerk: f64,
+    last_speed: f64,
+    last_angle: f64,
+    last_acceleration: (f64, f64),
+    straddling: bool,
+}
+
+impl DrivingStats {
+    pub fn new(start_station: f64) -> Self {
+        Self {
+            start_station,
+            station: start_station,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, car: &Car, road: &Road) {
+        if self.damaged_at.is_some() {
+            return;
+        }
+
+        if let Some(collision) = &car.collision {
+            self.hardest_impact = self.hardest_impact.max(collision.impact_speed);
+            if !car.damaged {
+                self.scrapes += 1;
+            }
+        }
+
+        if car.damaged {
+            self.damaged_at = Some(self.ticks);
+            return;
+        }
+
+        let (station, lateral) = road.project(Coord { x: car.x, y: car.y });
+        self.station = station;
+        self.lateral = lateral;
+
+        let nearest_lane = road.lane_offset(road.nearest_lane(lateral));
+        self.lane_deviation = (lateral - nearest_lane) / road.lane_width();
+        self.lane_offset += self.lane_deviation.abs();
+        let road_heading = road.point_at(station).1;
+        self.heading = (car.angle - road_heading + PI).rem_euclid(2.0 * PI) - PI;
+
+        let straddling = (lateral - nearest_lane).abs() > (road.lane_width() - car.width) / 2.0;
+        if straddling && !self.straddling {
+            self.lane_violations += 1;
+        }
+        self.straddling = straddling;
+
+        let acceleration = (
+            car.speed - self.last_speed,
+            car.speed * (car.angle - self.last_angle),
+        );
+        if self.ticks > 0 {
+            self.jerk += (acceleration.0 - self.last_acceleration.0)
+                .hypot(acceleration.1 - self.last_acceleration.1);
+        }
+
+        self.last_speed = car.speed;
+        self.last_angle = car.angle;
+        self.last_acceleration = acceleration;
+        self.ticks += 1;
+    }
+
+    /// Distance travelled along the road.
+    pub fn distance(&self) -> f64 {
+        self.station - self.start_station
+    }
+}
+
+/// Scores a car at the current tick. Higher is better; the car with the
+/// highest score is the one the next generation is bred from.
+pub trait Fitness: Debug {
+    fn name(&self) -> &'static str;
+
+    fn score(&self, car: &Car, world: &World) -> f64;
+}
+
+/// Distance travelled along the road, the original "minimum y wins" rule.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Distance;
+
+impl Fitness for Distance {
+    fn name(&self) -> &'static str {
+        "distance"
+    }
+
+    fn score(&self, car: &Car, _: &World) -> f64 {
+        car.stats.distance()
+    }
+}
+
+/// Distance travelled, minus a fixed penalty once the car is damaged and a
+/// smaller one for every tick spent scraping along something.
+#[derive(Debug, Clone, Copy)]
+pub struct CrashPenalty {
+    pub penalty: f64,
+    pub scrape_penalty: f64,
+}
+
+impl Default for CrashPenalty {
+    fn default() -> Self {
+        Self {
+            penalty: 500.0,
+            scrape_penalty: 5.0,
+        }
+    }
+}
+
+impl Fitness for CrashPenalty {
+    fn name(&self) -> &'static str {
+        "crash-penalty"
+    }
+
+    fn score(&self, car: &Car, _: &World) -> f64 {
+        let penalty = if car.damaged { self.penalty } else { 0.0 };
+        car.stats.distance() - penalty - self.scrape_penalty * car.stats.scrapes as f64
+    }
+}
+
+/// Number of traffic cars left behind. Ties are broken by distance.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Overtaken;
+
+impl Fitness for Overtaken {
+    fn name(&self) -> &'static str {
+        "overtaken"
+    }
+
+    fn score(&self, car: &Car, world: &World) -> f64 {
+        let (traffic, spawner) = world.traffic_of(car);
+        let overtaken = traffic
+            .iter()
+            .filter(|t| t.stats.station < car.stats.station)
+            .count()
+            + spawner.map_or(0, |spawner| {
+                spawner
+                    .removed
+                    .iter()
+                    .filter(|&&station| station < car.stats.station)
+                    .count()
+            });
+        overtaken as f64 + car.stats.distance().max(0.0) * 1e-6
+    }
+}
+
+/// Distance travelled, minus the time spent away from a lane center.
+#[derive(Debug, Clone, Copy)]
+pub struct LaneKeeping {
+    pub weight: f64,
+}
+
+impl Default for LaneKeeping {
+    fn default() -> Self {
+        Self { weight: 2.0 }
+    }
+}
+
+impl Fitness for LaneKeeping {
+    fn name(&self) -> &'static str {
+        "lane-keeping"
+    }
+
+    fn score(&self, car: &Car, _: &World) -> f64 {
+        car.stats.distance() - self.weight * car.stats.lane_offset
+    }
+}
+
+/// Distance travelled, minus the accumulated jerk of the ride.
+#[derive(Debug, Clone, Copy)]
+pub struct Comfort {
+    pub weight: f64,
+}
+
+impl Default for Comfort {
+    fn default() -> Self {
+        Self { weight: 5.0 }
+    }
+}
+
+impl Fitness for Comfort {
+    fn name(&self) -> &'static str {
+        "comfort"
+    }
+
+    fn score(&self, car: &Car, _: &World) -> f64 {
+        car.stats.distance() - self.weight * car.stats.jerk
+    }
+}
+
+pub fn built_in() -> Vec<Rc<dyn Fitness>> {
+    vec![
+        Rc::new(Distance),
+        Rc::new(CrashPenalty::default()),
+        Rc::new(Overtaken),
+        Rc::new(LaneKeeping::default()),
+        Rc::new(Comfort::default()),
+    ]
+}
+
+pub fn from_name(name: &str) -> Option<Rc<dyn Fitness>> {
+    built_in()
+        .into_iter()
+        .find(|fitness| fitness.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::from_name;
+    use crate::{
+        car::Car,
+        controls::ControlKind,
+        road::Road,
+        traffic::{TrafficConfig, TrafficSpawner},
+        world::World,
+    };
+
+    /// A car starting at `x` that moves `speed` up the road on each tick,
+    /// recorded as it goes.
+    fn drive(road: &Road, x: f64, speeds: &[f64]) -> Car {
+        let mut car = Car::new(x, 100.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        for &speed in speeds {
+            car.speed = speed;
+            car.y -= speed;
+            car.stats.record(&car.clone(), road);
+        }
+        car
+    }
+
+    #[test]
+    fn test_distance() {
+        let road = Road::new(100.0, 180.0, None);
+        let car = drive(&road, 100.0, &[2.0; 100]);
+        let world = World::new(road, Vec::new(), vec![car.clone()], 0);
+
+        let fitness = from_name("distance").unwrap();
+        assert_eq!(fitness.score(&car, &world), 200.0);
+    }
+
+    #[test]
+    fn test_crash_penalty() {
+        let road = Road::new(100.0, 180.0, None);
+        let mut car = Car::new(100.0, 100.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        car.y = -100.0;
+        car.stats.record(&car.clone(), &road);
+        let world = World::new(road, Vec::new(), vec![car.clone()], 0);
+
+        let fitness = from_name("crash-penalty").unwrap();
+        assert_eq!(fitness.score(&car, &world), 200.0);
+
+        car.damaged = true;
+        assert_eq!(fitness.score(&car, &world), -300.0);
+    }
+
+    #[test]
+    fn test_overtaken() {
+        let road = Road::new(100.0, 180.0, None);
+        let car = drive(&road, 100.0, &[2.0; 100]);
+        // One car behind the AI car, at station 50, and one ahead, at 500.
+        let traffic = [-50.0, -500.0]
+            .map(|y| Car::new(100.0, y, 30.0, 50.0, ControlKind::Dummy, None, None, None));
+        let mut world = World::new(road, traffic.to_vec(), vec![car.clone()], 0);
+
+        let fitness = from_name("overtaken").unwrap();
+        assert_eq!(fitness.score(&car, &world).floor(), 1.0);
+
+        // The car passed three of the removed cars, but not the fourth.
+        let mut spawner = TrafficSpawner::new(TrafficConfig::default(), 0);
+        spawner.removed = vec![-900.0, -800.0, -700.0, 150.0];
+        world.spawner = Some(spawner);
+        assert_eq!(fitness.score(&car, &world).floor(), 4.0);
+        // Ties are broken by distance.
+        assert!(fitness.score(&car, &world) > 4.0);
+
+        let mut stuck = Car::new(100.0, 100.0, 30.0, 50.0, ControlKind::AI, None, None, None);
+        stuck.stats.station = -850.0;
+        assert_eq!(fitness.score(&stuck, &world).floor(), 1.0);
+    }
+
+    #[test]
+    fn test_lane_keeping() {
+        let road = Road::new(100.0, 180.0, None);
+        let center = road.lane_center(1, 0.0).x;
+        let centered = drive(&road, center, &[2.0; 100]);
+        let straddling = drive(&road, center + road.lane_width() / 2.0, &[2.0; 100]);
+        assert_eq!(straddling.stats.lane_violations, 1);
+        let world = World::new(
+            road,
+            Vec::new(),
+            vec![centered.clone(), straddling.clone()],
+            0,
+        );
+
+        let fitness = from_name("lane-keeping").unwrap();
+        assert_eq!(fitness.score(&centered, &world), 200.0);
+        assert!(fitness.score(&centered, &world) > fitness.score(&straddling, &world));
+    }
+
+    #[test]
+    fn test_comfort() {
+        let road = Road::new(100.0, 180.0, None);
+        let smooth = drive(&road, 100.0, &[2.0; 100]);
+        let jerky = drive(&road, 100.0, &[1.0, 3.0].repeat(50));
+        assert_eq!(smooth.stats.distance(), jerky.stats.distance());
+        let world = World::new(road, Vec::new(), vec![smooth.clone(), jerky.clone()], 0);
+
+        let fitness = from_name("comfort").unwrap();
+        assert!(fitness.score(&smooth, &world) > fitness.score(&jerky, &world));
+    }
+}
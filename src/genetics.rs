@@ -0,0 +1,318 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use rand::Rng;
+
+use crate::network::NeuralNetwork;
+
+/// How parents are picked from the scored population.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    /// The fittest of `size` randomly drawn individuals.
+    Tournament { size: usize },
+    /// Chance proportional to fitness, shifted so the worst individual still
+    /// has a small chance.
+    Roulette,
+}
+
+/// How two parents are combined into a child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossover {
+    /// Every parameter comes from either parent with equal chance.
+    Uniform,
+    /// Parameters up to a random cut come from the first parent, the rest
+    /// from the second.
+    SinglePoint,
+}
+
+/// Mutation amount passed to [`NeuralNetwork::mutate`] for a given generation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MutationSchedule {
+    Constant(f64),
+    /// Goes linearly from `start` to `end` over `generations`, then stays at `end`.
+    Linear {
+        start: f64,
+        end: f64,
+        generations: usize,
+    },
+    /// `start * decay^generation`, never going below `min`.
+    Exponential {
+        start: f64,
+        decay: f64,
+        min: f64,
+    },
+}
+
+impl MutationSchedule {
+    pub fn amount(&self, generation: usize) -> f64 {
+        match *self {
+            Self::Constant(amount) => amount,
+            Self::Linear {
+                start,
+                end,
+                generations,
+            } => {
+                let t = if generations == 0 {
+                    1.0
+                } else {
+                    (generation as f64 / generations as f64).min(1.0)
+                };
+                start + (end - start) * t
+            }
+            Self::Exponential { start, decay, min } => {
+                (start * decay.powi(generation as i32)).max(min)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeneticConfig {
+    pub selection: Selection,
+    pub crossover: Crossover,
+    /// Chance that a child is bred from two parents rather than copied from one.
+    pub crossover_rate: f64,
+    /// The top `elitism` brains are carried over unchanged.
+    pub elitism: usize,
+    pub mutation: MutationSchedule,
+}
+
+impl Default for GeneticConfig {
+    fn default() -> Self {
+        Self {
+            selection: Selection::Tournament { size: 3 },
+            crossover: Crossover::Uniform,
+            crossover_rate: 0.7,
+            elitism: 1,
+            mutation: MutationSchedule::Constant(0.1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeMismatch {
+    pub left: Vec<usize>,
+    pub right: Vec<usize>,
+}
+
+impl fmt::Display for ShapeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot cross networks of shapes {:?} and {:?}",
+            self.left, self.right
+        )
+    }
+}
+
+impl Error for ShapeMismatch {}
+
+pub fn select(fitness: &[f64], selection: Selection, rng: &mut impl Rng) -> usize {
+    match selection {
+        Selection::Tournament { size } => (0..size.max(1))
+            .map(|_| rng.gen_range(0..fitness.len()))
+            .max_by(|&a, &b| fitness[a].total_cmp(&fitness[b]))
+            .unwrap(),
+        Selection::Roulette => {
+            let min = fitness.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = fitness.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let floor = (max - min) * 0.01 + f64::EPSILON;
+            let weights = fitness.iter().map(|f| f - min + floor);
+            let total: f64 = weights.clone().sum();
+
+            let mut pick = rng.gen_range(0.0..total);
+            for (i, weight) in weights.enumerate() {
+                if pick < weight {
+                    return i;
+                }
+                pick -= weight;
+            }
+            fitness.len() - 1
+        }
+    }
+}
+
+pub fn crossover(
+    a: &NeuralNetwork,
+    b: &NeuralNetwork,
+    crossover: Crossover,
+    rng: &mut impl Rng,
+) -> Result<NeuralNetwork, ShapeMismatch> {
+    if a.shape() != b.shape() {
+        return Err(ShapeMismatch {
+            left: a.shape(),
+            right: b.shape(),
+        });
+    }
+
+    let mut child = a.clone();
+    let cut = rng.gen_range(0..=a.parameters().count());
+    for (i, (gene, other)) in child.parameters_mut().zip(b.parameters()).enumerate() {
+        let take_other = match crossover {
+            Crossover::Uniform => rng.gen_bool(0.5),
+            Crossover::SinglePoint => i >= cut,
+        };
+        if take_other {
+            *gene = *other;
+        }
+    }
+
+    Ok(child)
+}
+
+/// Breeds a population of the same size from `brains`, where `fitness[i]` is
+/// the score of `brains[i]`. The elite come first, fittest first.
+pub fn breed(
+    brains: &[NeuralNetwork],
+    fitness: &[f64],
+    generation: usize,
+    config: &GeneticConfig,
+    rng: &mut impl Rng,
+) -> Vec<NeuralNetwork> {
+    let mut ranked = (0..brains.len()).collect::<Vec<usize>>();
+    ranked.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+
+    let mut next = ranked
+        .iter()
+        .take(config.elitism)
+        .map(|&i| brains[i].clone())
+        .collect::<Vec<NeuralNetwork>>();
+
+    let amount = config.mutation.amount(generation);
+    while next.len() < brains.len() {
+        let a = &brains[select(fitness, config.selection, rng)];
+        let mut child = if rng.gen_bool(config.crossover_rate.clamp(0.0, 1.0)) {
+            let b = &brains[select(fitness, config.selection, rng)];
+            crossover(a, b, config.crossover, rng).unwrap_or_else(|_| a.clone())
+        } else {
+            a.clone()
+        };
+        child.mutate(Some(amount), rng);
+        next.push(child);
+    }
+
+    next
+}
+
+fn parse_fields<const N: usize>(s: &str) -> Result<[f64; N], String> {
+    let fields = s
+        .split(':')
+        .map(|field| {
+            field
+                .parse::<f64>()
+                .map_err(|err| format!("`{field}`: {err}"))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    fields
+        .try_into()
+        .map_err(|_| format!("expected {N} values separated by `:` in `{s}`"))
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    /// `tournament`, `tournament:<size>` or `roulette`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "tournament" => Ok(Self::Tournament { size: 3 }),
+            None if s == "roulette" => Ok(Self::Roulette),
+            Some(("tournament", size)) => Ok(Self::Tournament {
+                size: size.parse().map_err(|err| format!("`{size}`: {err}"))?,
+            }),
+            _ => Err(format!("unknown selection `{s}`")),
+        }
+    }
+}
+
+impl FromStr for Crossover {
+    type Err = String;
+
+    /// `uniform` or `single-point`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(Self::Uniform),
+            "single-point" => Ok(Self::SinglePoint),
+            _ => Err(format!("unknown crossover `{s}`")),
+        }
+    }
+}
+
+impl FromStr for MutationSchedule {
+    type Err = String;
+
+    /// `<amount>`, `linear:<start>:<end>:<generations>` or
+    /// `exp:<start>:<decay>:<min>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("linear", rest)) => {
+                let [start, end, generations] = parse_fields(rest)?;
+                Ok(Self::Linear {
+                    start,
+                    end,
+                    generations: generations as usize,
+                })
+            }
+            Some(("exp", rest)) => {
+                let [start, decay, min] = parse_fields(rest)?;
+                Ok(Self::Exponential { start, decay, min })
+            }
+            _ => s
+                .parse()
+                .map(Self::Constant)
+                .map_err(|_| format!("unknown mutation schedule `{s}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{breed, crossover, Crossover, GeneticConfig, MutationSchedule};
+    use crate::network::NeuralNetwork;
+
+    #[test]
+    fn test_crossover_takes_genes_from_parents() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let a = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+        let b = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+
+        for kind in [Crossover::Uniform, Crossover::SinglePoint] {
+            let child = crossover(&a, &b, kind, &mut rng).unwrap();
+            let mut genes = child.parameters().zip(a.parameters().zip(b.parameters()));
+            assert!(genes.all(|(c, (a, b))| c == a || c == b));
+        }
+
+        let c = NeuralNetwork::new(vec![7, 6, 4], &mut rng);
+        assert!(crossover(&a, &c, Crossover::Uniform, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_breed_keeps_elite() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let brains = (0..10)
+            .map(|_| NeuralNetwork::new(vec![5, 6, 4], &mut rng))
+            .collect::<Vec<NeuralNetwork>>();
+        let fitness = (0..10).map(|i| i as f64).collect::<Vec<f64>>();
+        let config = GeneticConfig {
+            elitism: 2,
+            ..Default::default()
+        };
+
+        let next = breed(&brains, &fitness, 0, &config, &mut rng);
+        assert_eq!(next.len(), 10);
+        assert_eq!(next[0].levels[0].weights, brains[9].levels[0].weights);
+        assert_eq!(next[1].levels[0].weights, brains[8].levels[0].weights);
+    }
+
+    #[test]
+    fn test_mutation_schedule() {
+        let linear: MutationSchedule = "linear:0.5:0.1:4".parse().unwrap();
+        assert_eq!(linear.amount(0), 0.5);
+        assert!((linear.amount(2) - 0.3).abs() < 1e-12);
+        assert!((linear.amount(10) - 0.1).abs() < 1e-12);
+
+        let exp: MutationSchedule = "exp:0.4:0.5:0.05".parse().unwrap();
+        assert_eq!(exp.amount(1), 0.2);
+        assert_eq!(exp.amount(10), 0.05);
+    }
+}
@@ -0,0 +1,22 @@
+pub mod agent;
+pub mod app;
+pub mod benchmark;
+pub mod brain;
+pub mod car;
+pub mod controls;
+pub mod damage;
+pub mod fitness;
+pub mod genetics;
+pub mod library;
+pub mod network;
+pub mod noise;
+pub mod render;
+pub mod road;
+pub mod scenario;
+pub mod sensor;
+pub mod spatial;
+pub mod traffic;
+pub mod utils;
+pub mod vehicle;
+pub mod visualizer;
+pub mod world;
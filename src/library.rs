@@ -0,0 +1,326 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::network::NeuralNetwork;
+
+/// Slot the best brain of every generation is written to. Unlike named
+/// slots it is overwritten rather than versioned.
+pub const AUTOSAVE: &str = "autosave";
+
+/// Identifies one saved brain: every save under a name adds a new version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotId {
+    pub name: String,
+    pub version: usize,
+}
+
+/// Where a saved brain came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotMetadata {
+    pub generation: usize,
+    pub fitness: f64,
+    /// Name of the fitness function `fitness` was scored with.
+    pub fitness_name: String,
+    pub seed: u64,
+    /// When the brain was saved, as an ISO 8601 date.
+    pub saved_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrainSlot {
+    pub id: SlotId,
+    #[serde(with = "crate::brain::compact")]
+    pub brain: NeuralNetwork,
+    /// Missing for brains imported from before the library existed.
+    pub metadata: Option<SlotMetadata>,
+}
+
+impl BrainSlot {
+    pub fn shape(&self) -> Vec<usize> {
+        self.brain.shape()
+    }
+}
+
+/// How two saved brains differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub same_shape: bool,
+    /// Mean absolute difference of the parameters, when the shapes match.
+    pub parameter_distance: Option<f64>,
+    /// `b`'s fitness minus `a`'s, when both are known and were scored alike.
+    pub fitness_gain: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LibraryError {
+    NotFound(SlotId),
+    NameTaken(String),
+    /// [`AUTOSAVE`], which only the autosave may use.
+    Reserved(String),
+}
+
+impl fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(id) => write!(f, "no brain saved as {} v{}", id.name, id.version),
+            Self::NameTaken(name) => write!(f, "there already are brains named {name}"),
+            Self::Reserved(name) => write!(f, "{name} is reserved for the autosave"),
+        }
+    }
+}
+
+impl Error for LibraryError {}
+
+/// Every saved brain, and which one new populations start from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BrainLibrary {
+    pub slots: Vec<BrainSlot>,
+    /// Slot loaded or saved since the last autosave. Without one,
+    /// populations start from the autosave.
+    pub active: Option<SlotId>,
+}
+
+impl BrainLibrary {
+    pub fn get(&self, id: &SlotId) -> Option<&BrainSlot> {
+        self.slots.iter().find(|slot| &slot.id == id)
+    }
+
+    /// Saves `brain` as the next version of `name` and makes it active.
+    pub fn save(
+        &mut self,
+        name: &str,
+        brain: NeuralNetwork,
+        metadata: Option<SlotMetadata>,
+    ) -> Result<SlotId, LibraryError> {
+        if name == AUTOSAVE {
+            return Err(LibraryError::Reserved(name.to_string()));
+        }
+
+        let version = self
+            .slots
+            .iter()
+            .filter(|slot| slot.id.name == name)
+            .map(|slot| slot.id.version)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let id = SlotId {
+            name: name.to_string(),
+            version,
+        };
+
+        self.slots.push(BrainSlot {
+            id: id.clone(),
+            brain,
+            metadata,
+        });
+        self.active = Some(id.clone());
+        Ok(id)
+    }
+
+    /// Replaces the autosaved brain. It descends from whatever was active,
+    /// so populations carry on from it instead.
+    pub fn autosave(&mut self, brain: NeuralNetwork, metadata: Option<SlotMetadata>) {
+        self.slots.retain(|slot| slot.id.name != AUTOSAVE);
+        self.active = None;
+        self.slots.push(BrainSlot {
+            id: SlotId {
+                name: AUTOSAVE.to_string(),
+                version: 1,
+            },
+            brain,
+            metadata,
+        });
+    }
+
+    /// Makes `id` the brain new populations start from.
+    pub fn load(&mut self, id: &SlotId) -> Result<&NeuralNetwork, LibraryError> {
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| &slot.id == id)
+            .ok_or_else(|| LibraryError::NotFound(id.clone()))?;
+        self.active = Some(id.clone());
+        Ok(&self.slots[index].brain)
+    }
+
+    /// The active brain, or the autosave when none is active.
+    pub fn starting_brain(&self) -> Option<&NeuralNetwork> {
+        let autosave = SlotId {
+            name: AUTOSAVE.to_string(),
+            version: 1,
+        };
+        self.get(self.active.as_ref().unwrap_or(&autosave))
+            .map(|slot| &slot.brain)
+    }
+
+    /// Renames every version of `name`.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), LibraryError> {
+        if new_name == AUTOSAVE {
+            return Err(LibraryError::Reserved(new_name.to_string()));
+        }
+        if self.slots.iter().any(|slot| slot.id.name == new_name) {
+            return Err(LibraryError::NameTaken(new_name.to_string()));
+        }
+
+        for id in self
+            .slots
+            .iter_mut()
+            .map(|slot| &mut slot.id)
+            .chain(self.active.as_mut())
+            .filter(|id| id.name == name)
+        {
+            id.name = new_name.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn delete(&mut self, id: &SlotId) -> Result<BrainSlot, LibraryError> {
+        let index = self
+            .slots
+            .iter()
+            .position(|slot| &slot.id == id)
+            .ok_or_else(|| LibraryError::NotFound(id.clone()))?;
+        if self.active.as_ref() == Some(id) {
+            self.active = None;
+        }
+        Ok(self.slots.remove(index))
+    }
+
+    /// Forgets the autosave and the active slot, so new populations start
+    /// from random brains. Named slots are kept.
+    pub fn discard(&mut self) {
+        self.slots.retain(|slot| slot.id.name != AUTOSAVE);
+        self.active = None;
+    }
+
+    pub fn compare(&self, a: &SlotId, b: &SlotId) -> Result<Comparison, LibraryError> {
+        let find = |id: &SlotId| {
+            self.get(id)
+                .ok_or_else(|| LibraryError::NotFound(id.clone()))
+        };
+        let (a, b) = (find(a)?, find(b)?);
+
+        let same_shape = a.shape() == b.shape();
+        let parameter_distance = same_shape.then(|| {
+            let count = a.brain.parameters().count().max(1);
+            a.brain
+                .parameters()
+                .zip(b.brain.parameters())
+                .map(|(a, b)| (a - b).abs())
+                .sum::<f64>()
+                / count as f64
+        });
+        let fitness_gain = match (&a.metadata, &b.metadata) {
+            (Some(a), Some(b)) if a.fitness_name == b.fitness_name => Some(b.fitness - a.fitness),
+            _ => None,
+        };
+
+        Ok(Comparison {
+            same_shape,
+            parameter_distance,
+            fitness_gain,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{BrainLibrary, LibraryError, SlotId, SlotMetadata, AUTOSAVE};
+    use crate::network::NeuralNetwork;
+
+    fn metadata(fitness: f64) -> Option<SlotMetadata> {
+        Some(SlotMetadata {
+            generation: 3,
+            fitness,
+            fitness_name: "distance".to_string(),
+            seed: 7,
+            saved_at: "2024-01-01T00:00:00.000Z".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_saves_are_versioned_not_overwritten() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut library = BrainLibrary::default();
+        let first = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+        let second = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+
+        library.autosave(first.clone(), None);
+        assert_eq!(library.starting_brain(), Some(&first));
+
+        let v1 = library
+            .save("good", first.clone(), metadata(100.0))
+            .unwrap();
+        let v2 = library
+            .save("good", second.clone(), metadata(150.0))
+            .unwrap();
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert_eq!(library.get(&v1).unwrap().brain, first);
+        assert_eq!(library.starting_brain(), Some(&second));
+
+        let comparison = library.compare(&v1, &v2).unwrap();
+        assert!(comparison.same_shape && comparison.parameter_distance.unwrap() > 0.0);
+        assert_eq!(comparison.fitness_gain, Some(50.0));
+
+        library.rename("good", "best").unwrap();
+        let renamed = SlotId {
+            name: "best".to_string(),
+            version: 2,
+        };
+        assert_eq!(library.active, Some(renamed.clone()));
+        assert_eq!(
+            library.rename("best", AUTOSAVE),
+            Err(LibraryError::Reserved(AUTOSAVE.to_string()))
+        );
+
+        library.delete(&renamed).unwrap();
+        assert_eq!(library.active, None);
+        assert!(library.delete(&renamed).is_err());
+        assert_eq!(library.load(&v1), Err(LibraryError::NotFound(v1)));
+
+        let third = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+        library
+            .load(&SlotId {
+                name: "best".to_string(),
+                version: 1,
+            })
+            .unwrap();
+        library.autosave(third.clone(), None);
+        assert_eq!(library.starting_brain(), Some(&third));
+
+        let json = serde_json::to_string(&library).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BrainLibrary>(&json).unwrap(),
+            library
+        );
+        assert_eq!(
+            library
+                .slots
+                .iter()
+                .filter(|slot| slot.id.name == AUTOSAVE)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_autosave_name_is_reserved() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut library = BrainLibrary::default();
+        let brain = NeuralNetwork::new(vec![5, 6, 4], &mut rng);
+
+        assert_eq!(
+            library.save(AUTOSAVE, brain.clone(), None),
+            Err(LibraryError::Reserved(AUTOSAVE.to_string()))
+        );
+        let mine = library.save("mine", brain.clone(), None).unwrap();
+        assert!(library.rename("mine", AUTOSAVE).is_err());
+
+        library.autosave(brain, None);
+        assert!(library.get(&mine).is_some());
+    }
+}
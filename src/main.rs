@@ -1,13 +1,4 @@
-use app::App;
-
-pub mod app;
-pub mod car;
-pub mod controls;
-pub mod network;
-pub mod road;
-pub mod sensor;
-pub mod utils;
-pub mod visualizer;
+use self_driving_car::app::App;
 
 fn main() {
     yew::Renderer::<App>::new().render();
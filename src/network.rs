@@ -1,21 +1,99 @@
-use std::vec;
+use std::{str::FromStr, vec};
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::utils::lerp;
+use crate::{
+    brain::{BrainError, BrainFile, Precision},
+    controls::ControlMode,
+    sensor::SensorConfig,
+    utils::lerp,
+};
+
+/// Applied to `sum - bias` for every output of a [`Level`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Activation {
+    /// 1 if the weighted sum exceeds the bias, 0 otherwise.
+    #[default]
+    Step,
+    Sigmoid,
+    Tanh,
+    Relu,
+    LeakyRelu,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 5] = [
+        Self::Step,
+        Self::Sigmoid,
+        Self::Tanh,
+        Self::Relu,
+        Self::LeakyRelu,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Step => "step",
+            Self::Sigmoid => "sigmoid",
+            Self::Tanh => "tanh",
+            Self::Relu => "relu",
+            Self::LeakyRelu => "leaky-relu",
+        }
+    }
+
+    /// Whether the activation can output negative values.
+    pub fn is_signed(self) -> bool {
+        matches!(self, Self::Tanh | Self::LeakyRelu)
+    }
+
+    pub fn apply(self, x: f64) -> f64 {
+        match self {
+            Self::Step => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Self::Tanh => x.tanh(),
+            Self::Relu => x.max(0.0),
+            Self::LeakyRelu => {
+                if x > 0.0 {
+                    x
+                } else {
+                    0.01 * x
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Activation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|activation| activation.name() == s)
+            .ok_or_else(|| format!("unknown activation `{s}`"))
+    }
+}
 
 #[repr(C)]
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Level {
     pub inputs: Vec<f64>,
     pub outputs: Vec<f64>,
     pub biases: Vec<f64>,
     pub weights: Vec<Vec<f64>>,
+    #[serde(default)]
+    pub activation: Activation,
 }
 
 impl Level {
-    pub fn new(input_count: usize, output_count: usize) -> Self {
+    pub fn new(input_count: usize, output_count: usize, rng: &mut impl Rng) -> Self {
         let inputs = vec![0.0; input_count];
         let outputs = vec![0.0; output_count];
         let biases = vec![0.0; output_count];
@@ -30,14 +108,14 @@ impl Level {
             outputs,
             biases,
             weights,
+            activation: Activation::default(),
         };
 
-        Self::randomize(&mut this);
+        this.randomize(rng);
         this
     }
 
-    pub fn randomize(&mut self) {
-        let mut rng = thread_rng();
+    pub fn randomize(&mut self, rng: &mut impl Rng) {
         for i in 0..self.inputs.len() {
             for j in 0..self.outputs.len() {
                 self.weights[i][j] = rng.gen_range(0.0..1.0) * 2.0 - 1.0;
@@ -60,11 +138,7 @@ impl Level {
                 sum += self.inputs[j] * self.weights[j][i];
             }
 
-            if sum > self.biases[i] {
-                self.outputs[i] = 1.0;
-            } else {
-                self.outputs[i] = 0.0;
-            }
+            self.outputs[i] = self.activation.apply(sum - self.biases[i]);
         }
 
         self.outputs.to_vec()
@@ -72,20 +146,30 @@ impl Level {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct NeuralNetwork {
     pub levels: Vec<Level>,
+    #[serde(default)]
+    pub control_mode: ControlMode,
+    /// Sensor whose readings are the inputs; brains from before it was
+    /// stored used the default one.
+    #[serde(default)]
+    pub sensor: SensorConfig,
 }
 
 impl NeuralNetwork {
-    pub fn new(neuron_counts: Vec<usize>) -> Self {
+    pub fn new(neuron_counts: Vec<usize>, rng: &mut impl Rng) -> Self {
         let mut levels = Vec::new();
 
         for i in 0..neuron_counts.len() - 1 {
-            levels.push(Level::new(neuron_counts[i], neuron_counts[i + 1]));
+            levels.push(Level::new(neuron_counts[i], neuron_counts[i + 1], rng));
         }
 
-        Self { levels }
+        Self {
+            levels,
+            control_mode: ControlMode::default(),
+            sensor: SensorConfig::default(),
+        }
     }
 
     pub fn feed_forward(&mut self, given_inputs: Vec<f64>) -> Vec<f64> {
@@ -97,8 +181,53 @@ impl NeuralNetwork {
         outputs
     }
 
-    pub fn mutate(&mut self, amount: Option<f64>) {
-        let mut rng = thread_rng();
+    /// The brain file format shared by the web UI's export and the trainer.
+    pub fn to_json(&self) -> String {
+        BrainFile::new(self.clone()).to_json()
+    }
+
+    /// Reads and validates a brain file, migrating older versions.
+    pub fn from_json(text: &str) -> Result<Self, BrainError> {
+        BrainFile::parse(text).map(|file| file.network)
+    }
+
+    /// The compact binary brain file format.
+    pub fn to_bytes(&self, precision: Precision) -> Vec<u8> {
+        BrainFile::new(self.clone()).to_bytes(precision)
+    }
+
+    /// Reads a brain file written as JSON, binary or base64 binary.
+    pub fn decode(bytes: &[u8]) -> Result<Self, BrainError> {
+        BrainFile::decode(bytes).map(|file| file.network)
+    }
+
+    /// Neuron counts per layer, as passed to [`NeuralNetwork::new`].
+    pub fn shape(&self) -> Vec<usize> {
+        let mut shape = Vec::new();
+        if let Some(level) = self.levels.first() {
+            shape.push(level.inputs.len());
+        }
+        shape.extend(self.levels.iter().map(|level| level.outputs.len()));
+        shape
+    }
+
+    /// Every bias and weight, level by level, biases first.
+    pub fn parameters(&self) -> impl Iterator<Item = &f64> {
+        self.levels
+            .iter()
+            .flat_map(|level| level.biases.iter().chain(level.weights.iter().flatten()))
+    }
+
+    pub fn parameters_mut(&mut self) -> impl Iterator<Item = &mut f64> {
+        self.levels.iter_mut().flat_map(|level| {
+            level
+                .biases
+                .iter_mut()
+                .chain(level.weights.iter_mut().flatten())
+        })
+    }
+
+    pub fn mutate(&mut self, amount: Option<f64>, rng: &mut impl Rng) {
         let amount = amount.unwrap_or(1.0);
 
         self.levels.iter_mut().for_each(|level| {
@@ -118,3 +247,100 @@ impl NeuralNetwork {
         });
     }
 }
+
+/// Layout of the brains given to a freshly generated population.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrainConfig {
+    /// Neuron counts of the hidden levels.
+    pub hidden: Vec<usize>,
+    /// One activation per level; missing entries fall back to the last one.
+    /// An analog brain's output level needs to go negative to brake and
+    /// steer right, so it gets [`Activation::Tanh`] unless its own is signed.
+    pub activations: Vec<Activation>,
+    pub control_mode: ControlMode,
+    /// Sets the input count.
+    pub sensor: SensorConfig,
+}
+
+impl Default for BrainConfig {
+    fn default() -> Self {
+        Self {
+            hidden: vec![6],
+            activations: vec![Activation::Step],
+            control_mode: ControlMode::Buttons,
+            sensor: SensorConfig::default(),
+        }
+    }
+}
+
+impl BrainConfig {
+    pub fn build(&self, rng: &mut impl Rng) -> NeuralNetwork {
+        let mut neuron_counts = vec![self.sensor.input_count()];
+        neuron_counts.extend(&self.hidden);
+        neuron_counts.push(self.control_mode.output_count());
+
+        let mut network = NeuralNetwork::new(neuron_counts, rng);
+        for (i, level) in network.levels.iter_mut().enumerate() {
+            level.activation = self
+                .activations
+                .get(i)
+                .or(self.activations.last())
+                .copied()
+                .unwrap_or_default();
+        }
+        if let Some(output) = network.levels.last_mut() {
+            if self.control_mode == ControlMode::Analog && !output.activation.is_signed() {
+                output.activation = Activation::Tanh;
+            }
+        }
+        network.control_mode = self.control_mode;
+        network.sensor = self.sensor;
+
+        network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{Activation, BrainConfig, NeuralNetwork};
+    use crate::controls::ControlMode;
+
+    #[test]
+    fn test_analog_brains_can_steer_both_ways() {
+        let config = BrainConfig {
+            control_mode: ControlMode::Analog,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut steering = (0..20).flat_map(|_| {
+            let mut brain = config.build(&mut rng);
+            assert_eq!(brain.levels[0].activation, Activation::Step);
+            assert_eq!(brain.levels[1].activation, Activation::Tanh);
+            [0.0, 0.5, 1.0].map(|input| brain.feed_forward(vec![input; 5])[1])
+        });
+        assert!(steering.any(|steering| steering < 0.0));
+    }
+
+    #[test]
+    fn test_brain_without_activation_uses_step() {
+        let json = r#"{"levels":[{"inputs":[0.0,0.0],"outputs":[0.0],"biases":[0.5],"weights":[[1.0],[-1.0]]}]}"#;
+        let mut network: NeuralNetwork = serde_json::from_str(json).unwrap();
+
+        assert_eq!(network.levels[0].activation, Activation::Step);
+        assert_eq!(network.control_mode, ControlMode::Buttons);
+        assert_eq!(network.feed_forward(vec![1.0, 0.0]), vec![1.0]);
+        assert_eq!(network.feed_forward(vec![1.0, 1.0]), vec![0.0]);
+
+        network.levels[0].activation = Activation::Tanh;
+        assert_eq!(network.feed_forward(vec![1.0, 0.0]), vec![0.5f64.tanh()]);
+    }
+
+    #[test]
+    fn test_brain_file_round_trips() {
+        let brain = NeuralNetwork::from_json(include_str!("../brains/reference.json")).unwrap();
+        assert_eq!(NeuralNetwork::from_json(&brain.to_json()).unwrap(), brain);
+        assert!(NeuralNetwork::from_json("null").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::sensor::Sensor;
+
+/// How a sensor's readings are degraded before the brain sees them. Unlike
+/// the [`SensorConfig`](crate::sensor::SensorConfig) it is not part of the
+/// brain, so a brain trained on clean readings can be driven with noisy ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseConfig {
+    /// Standard deviation of the Gaussian noise added to each ray's range,
+    /// in ray lengths.
+    pub range_sigma: f64,
+    /// Chance that a ray reads nothing on a tick.
+    pub dropout: f64,
+    /// Distinct ranges a ray can report, or 0 for exact ranges.
+    pub range_levels: usize,
+    /// Ticks before the brain sees a reading.
+    pub latency: usize,
+}
+
+impl NoiseConfig {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Applies a [`NoiseConfig`] to one car's sensor, drawing from its own
+/// generator and remembering the readings the brain has yet to see.
+#[derive(Debug, Clone)]
+pub struct SensorNoise {
+    pub config: NoiseConfig,
+    rng: StdRng,
+    pending: VecDeque<Vec<f64>>,
+}
+
+impl SensorNoise {
+    pub fn new(config: NoiseConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// The inputs the brain gets this tick: the sensor's current ones with
+    /// noise, dropout and quantization applied, as they were `latency` ticks
+    /// ago. Until then it gets the oldest inputs there are.
+    pub fn apply(&mut self, sensor: &Sensor) -> Vec<f64> {
+        let config = self.config;
+        let modalities = sensor.config.modalities;
+        let stride = 1 + usize::from(modalities.hit_kind) + usize::from(modalities.closing_speed);
+        let mut inputs = sensor.inputs.clone();
+
+        for ray in inputs.chunks_mut(stride).take(sensor.config.ray_count) {
+            let dropped = self.rng.gen::<f64>() < config.dropout;
+            if ray[0] == 0.0 || dropped {
+                ray.fill(0.0);
+                continue;
+            }
+
+            let mut offset = 1.0 - ray[0];
+            if config.range_sigma > 0.0 {
+                offset = (offset + config.range_sigma * gaussian(&mut self.rng)).max(0.0);
+            }
+            if config.range_levels > 0 {
+                let levels = config.range_levels as f64;
+                offset = (offset * levels).round() / levels;
+            }
+
+            // Pushed out of range, the hit is lost.
+            if offset >= 1.0 {
+                ray.fill(0.0);
+            } else {
+                ray[0] = 1.0 - offset;
+            }
+        }
+
+        self.pending.push_back(inputs);
+        while self.pending.len() > config.latency + 1 {
+            self.pending.pop_front();
+        }
+        self.pending[0].clone()
+    }
+}
+
+/// A standard normal sample, by the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let (u, v) = (1.0 - rng.gen::<f64>(), rng.gen::<f64>());
+    (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NoiseConfig, SensorNoise};
+    use crate::sensor::{Modalities, Sensor, SensorConfig};
+
+    fn sensor(inputs: Vec<f64>) -> Sensor {
+        let mut sensor = Sensor::new(SensorConfig {
+            ray_count: 2,
+            modalities: "hit-kind,speed".parse::<Modalities>().unwrap(),
+            ..Default::default()
+        });
+        sensor.inputs = inputs;
+        sensor
+    }
+
+    #[test]
+    fn test_noise_degrades_only_ray_ranges() {
+        let clean = sensor(vec![0.5, 1.0, 0.0, 0.0, 0.8]);
+
+        let mut dropout = SensorNoise::new(
+            NoiseConfig {
+                dropout: 1.0,
+                ..Default::default()
+            },
+            0,
+        );
+        assert_eq!(dropout.apply(&clean), vec![0.0, 0.0, 0.0, 0.0, 0.8]);
+
+        let mut quantized = SensorNoise::new(
+            NoiseConfig {
+                range_levels: 4,
+                ..Default::default()
+            },
+            0,
+        );
+        assert_eq!(
+            quantized.apply(&sensor(vec![0.6, 1.0, 0.1, 0.0, 0.8])),
+            vec![0.5, 1.0, 0.0, 0.0, 0.8]
+        );
+
+        let mut noisy = SensorNoise::new(
+            NoiseConfig {
+                range_sigma: 0.1,
+                ..Default::default()
+            },
+            0,
+        );
+        let ranges = (0..100)
+            .map(|_| noisy.apply(&clean)[0])
+            .collect::<Vec<f64>>();
+        assert!(ranges.iter().any(|&range| range != 0.5));
+        assert!(ranges.iter().all(|&range| (0.0..=1.0).contains(&range)));
+        // A ray that hit nothing stays silent.
+        assert!((0..100).all(|_| noisy.apply(&clean)[2] == 0.0));
+    }
+
+    #[test]
+    fn test_latency_delays_readings() {
+        let mut delayed = SensorNoise::new(
+            NoiseConfig {
+                latency: 2,
+                ..Default::default()
+            },
+            0,
+        );
+        let seen = (1..=4)
+            .map(|tick| delayed.apply(&sensor(vec![tick as f64 / 4.0, 0.0, 0.0, 0.0, 0.0]))[0])
+            .collect::<Vec<f64>>();
+        assert_eq!(seen, vec![0.25, 0.25, 0.25, 0.5]);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use gloo::utils::document;
+use js_sys::{Array, Function};
+use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement};
+
+use crate::{
+    car::Car,
+    road::Road,
+    sensor::Sensor,
+    utils::{Coord, CoordWithOffset},
+};
+
+#[derive(Debug, Clone)]
+pub struct CarSprite {
+    pub img: HtmlImageElement,
+    pub mask: HtmlCanvasElement,
+}
+
+impl CarSprite {
+    pub fn new(color: &str, width: f64, height: f64) -> Self {
+        let img = HtmlImageElement::new().unwrap();
+        img.set_src("car.png");
+
+        let mask = document()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into::<HtmlCanvasElement>()
+            .unwrap();
+
+        mask.set_width(width as u32);
+        mask.set_height(height as u32);
+
+        let mask_ctx = mask
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<CanvasRenderingContext2d>()
+            .unwrap();
+
+        let onload = {
+            let color = color.to_string();
+            let img = img.clone();
+
+            Closure::<dyn Fn()>::new(move || {
+                mask_ctx.set_fill_style_str(&color);
+                mask_ctx.rect(0.0, 0.0, width, height);
+                mask_ctx.fill();
+
+                mask_ctx
+                    .set_global_composite_operation("destination-atop")
+                    .unwrap();
+                mask_ctx
+                    .draw_image_with_html_image_element_and_dw_and_dh(&img, 0.0, 0.0, width, height)
+                    .unwrap();
+            })
+            .into_js_value()
+            .dyn_into::<Function>()
+            .unwrap()
+        };
+
+        img.set_onload(Some(&onload));
+
+        Self { img, mask }
+    }
+}
+
+/// Sprites are shared by every car of the same color, so a population of
+/// identical AI cars only loads `car.png` once.
+#[derive(Debug, Clone, Default)]
+pub struct SpriteCache {
+    sprites: HashMap<String, CarSprite>,
+}
+
+impl SpriteCache {
+    pub fn get(&mut self, car: &Car) -> &CarSprite {
+        self.sprites
+            .entry(car.color.clone())
+            .or_insert_with(|| CarSprite::new(&car.color, car.width, car.height))
+    }
+}
+
+pub fn draw_road(ctx: &CanvasRenderingContext2d, road: &Road) {
+    ctx.set_line_width(5.0);
+    ctx.set_stroke_style_str("white");
+
+    for i in 1..=road.lane_count - 1 {
+        let offset = -road.width / 2.0 + i as f64 * road.lane_width();
+
+        let array = Array::new();
+        array.push(&JsValue::from(20));
+        array.push(&JsValue::from(20));
+
+        ctx.set_line_dash(&array).unwrap();
+        draw_polyline(ctx, &road.offset_line(offset));
+    }
+
+    ctx.set_line_dash(&Array::new()).unwrap();
+    for border in &road.borders {
+        draw_polyline(ctx, border);
+    }
+}
+
+fn draw_polyline(ctx: &CanvasRenderingContext2d, points: &[Coord]) {
+    ctx.begin_path();
+    for (i, point) in points.iter().enumerate() {
+        if i == 0 {
+            ctx.move_to(point.x, point.y);
+        } else {
+            ctx.line_to(point.x, point.y);
+        }
+    }
+    ctx.stroke();
+}
+
+pub fn draw_sensor(ctx: &CanvasRenderingContext2d, sensor: &Sensor) {
+    for i in 0..sensor.rays.len() {
+        let mut end = CoordWithOffset {
+            x: sensor.rays[i].1.x,
+            y: sensor.rays[i].1.y,
+            ..Default::default()
+        };
+        if let Some(reading) = sensor.readings[i] {
+            end = reading
+        }
+
+        ctx.begin_path();
+        ctx.set_line_width(2.0);
+        ctx.set_stroke_style_str("yellow");
+        ctx.move_to(sensor.rays[i].0.x, sensor.rays[i].0.y);
+        ctx.line_to(end.x, end.y);
+        ctx.stroke();
+        ctx.begin_path();
+        ctx.set_line_width(2.0);
+        ctx.set_stroke_style_str("black");
+        ctx.move_to(sensor.rays[i].1.x, sensor.rays[i].1.y);
+        ctx.line_to(end.x, end.y);
+        ctx.stroke();
+    }
+}
+
+pub fn draw_car(
+    ctx: &CanvasRenderingContext2d,
+    car: &Car,
+    sprite: &CarSprite,
+    draw_sensor: Option<bool>,
+) {
+    let draw_sensor = draw_sensor.unwrap_or_default();
+
+    if let Some(sensor) = &car.sensor {
+        if draw_sensor {
+            self::draw_sensor(ctx, sensor);
+        }
+    }
+
+    ctx.save();
+    ctx.translate(car.x, car.y).unwrap();
+    ctx.rotate(-car.angle).unwrap();
+    // Eliminated cars fade out behind a dashed outline, unlike grey wrecks.
+    if car.eliminated {
+        ctx.set_global_alpha(ctx.global_alpha() * 0.5);
+        let array = Array::new();
+        array.push(&JsValue::from(4));
+        array.push(&JsValue::from(4));
+        ctx.set_line_dash(&array).unwrap();
+        ctx.set_line_width(2.0);
+        ctx.set_stroke_style_str("black");
+        ctx.stroke_rect(-car.width / 2.0, -car.height / 2.0, car.width, car.height);
+    }
+    if !car.damaged {
+        ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
+            &sprite.mask,
+            -car.width / 2.0,
+            -car.height / 2.0,
+            car.width,
+            car.height,
+        )
+        .unwrap();
+        ctx.set_global_composite_operation("multiply").unwrap();
+    }
+    ctx.draw_image_with_html_image_element_and_dw_and_dh(
+        &sprite.img,
+        -car.width / 2.0,
+        -car.height / 2.0,
+        car.width,
+        car.height,
+    )
+    .unwrap();
+
+    ctx.restore();
+}
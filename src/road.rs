@@ -1,84 +1,356 @@
-use std::vec;
+use std::{f64::consts::PI, vec};
 
-use js_sys::Array;
-use wasm_bindgen::JsValue;
-use web_sys::CanvasRenderingContext2d;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::utils::{lerp, Coord};
 
+/// Distance between two samples of a generated centerline.
+const SAMPLE_LENGTH: f64 = 20.0;
+
+/// A road follows its centerline, a polyline running in the driving
+/// direction. Positions along it are measured by their *station*, the
+/// distance travelled along the centerline; stations are chosen so that on a
+/// straight road going up the screen the station of a point is `-y`.
 #[repr(C)]
 #[derive(Debug, Clone, Default)]
 pub struct Road {
     pub x: f64,
     pub width: f64,
     pub lane_count: usize,
-    pub left: f64,
-    pub right: f64,
-    pub top: f64,
-    pub bottom: f64,
+    pub centerline: Vec<Coord>,
+    /// Heading of the road at each centerline point, in the same convention
+    /// as `Car::angle`.
+    pub headings: Vec<f64>,
+    /// Station of each centerline point.
+    pub stations: Vec<f64>,
     pub borders: Vec<Vec<Coord>>,
+    generator: Option<RoadGenerator>,
+}
+
+/// Seeded source of an endless, curvy centerline made of straights and arcs.
+#[derive(Debug, Clone)]
+struct RoadGenerator {
+    seed: u64,
+    rng: StdRng,
+    position: Coord,
+    heading: f64,
+    station: f64,
+    curvature: f64,
+    remaining: f64,
+}
+
+impl RoadGenerator {
+    /// Sharpest curve, as 1 / radius.
+    const MAX_CURVATURE: f64 = 1.0 / 300.0;
+    /// The road never turns further than this away from straight up.
+    const MAX_HEADING: f64 = PI / 4.0;
+    /// Straight stretch before the first curve, so the starting grid and the
+    /// first traffic are on a straight road.
+    const LEAD_IN: f64 = 1_500.0;
+
+    fn new(x: f64, seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            position: Coord { x, y: 1_000.0 },
+            heading: 0.0,
+            station: -1_000.0,
+            curvature: 0.0,
+            remaining: Self::LEAD_IN,
+        }
+    }
+
+    /// Advances by one sample and returns the new point, heading and station.
+    fn next(&mut self) -> (Coord, f64, f64) {
+        if self.remaining <= 0.0 {
+            self.remaining = self.rng.gen_range(200.0..600.0);
+            self.curvature = if self.rng.gen_bool(0.3) {
+                0.0
+            } else {
+                self.rng
+                    .gen_range(-Self::MAX_CURVATURE..Self::MAX_CURVATURE)
+            };
+        }
+
+        // Bend back towards straight up before the road turns sideways.
+        if self.heading.abs() > Self::MAX_HEADING
+            && self.curvature.signum() == self.heading.signum()
+        {
+            self.curvature = -self.curvature;
+        }
+
+        let heading = self.heading + self.curvature * SAMPLE_LENGTH / 2.0;
+        self.position.x -= heading.sin() * SAMPLE_LENGTH;
+        self.position.y -= heading.cos() * SAMPLE_LENGTH;
+        self.heading += self.curvature * SAMPLE_LENGTH;
+        self.station += SAMPLE_LENGTH;
+        self.remaining -= SAMPLE_LENGTH;
+
+        (self.position, self.heading, self.station)
+    }
+}
+
+/// Unit vector pointing to the right of a car or road with `heading`.
+fn right_of(heading: f64) -> Coord {
+    Coord {
+        x: heading.cos(),
+        y: -heading.sin(),
+    }
 }
 
 impl Road {
     pub fn new(x: f64, width: f64, lane_count: Option<usize>) -> Self {
-        let lane_count = lane_count.unwrap_or(3);
-        let left = x - width / 2.0;
-        let right = x + width / 2.0;
         let infinity = 1_000_000.0;
-        let top = -infinity;
-        let bottom = infinity;
-
-        let top_left = Coord { x: left, y: top };
-        let top_right = Coord { x: right, y: top };
-        let bottom_left = Coord { x: left, y: bottom };
-        let bottom_right = Coord {
-            x: right,
-            y: bottom,
+        let bottom = Coord { x, y: infinity };
+        let top = Coord { x, y: -infinity };
+
+        Self::from_centerline(vec![bottom, top], width, lane_count)
+    }
+
+    /// A road along an arbitrary polyline, whose first point has station
+    /// `-y` so it lines up with a straight road.
+    pub fn from_centerline(centerline: Vec<Coord>, width: f64, lane_count: Option<usize>) -> Self {
+        let lane_count = lane_count.unwrap_or(3);
+
+        let mut stations = vec![-centerline[0].y];
+        for i in 1..centerline.len() {
+            let (a, b) = (centerline[i - 1], centerline[i]);
+            stations.push(stations[i - 1] + (b.x - a.x).hypot(b.y - a.y));
+        }
+
+        let segment_heading = |i: usize| {
+            let (a, b) = (centerline[i], centerline[i + 1]);
+            (a.x - b.x).atan2(a.y - b.y)
         };
+        let last = centerline.len() - 1;
+        let headings = (0..centerline.len())
+            .map(|i| match i {
+                0 => segment_heading(0),
+                i if i == last => segment_heading(last - 1),
+                i => (segment_heading(i - 1) + segment_heading(i)) / 2.0,
+            })
+            .collect();
 
-        let borders = vec![vec![top_left, bottom_left], vec![top_right, bottom_right]];
+        let mut this = Self {
+            x: centerline[0].x,
+            width,
+            lane_count,
+            centerline,
+            headings,
+            stations,
+            borders: Vec::new(),
+            generator: None,
+        };
+        this.update_borders();
+        this
+    }
 
-        Self {
+    /// An endless curvy road, generated from `seed` as cars drive along it.
+    pub fn procedural(x: f64, width: f64, lane_count: Option<usize>, seed: u64) -> Self {
+        let mut this = Self {
             x,
             width,
-            lane_count,
-            left,
-            right,
-            top,
-            bottom,
-            borders,
+            lane_count: lane_count.unwrap_or(3),
+            generator: Some(RoadGenerator::new(x, seed)),
+            ..Default::default()
+        };
+        this.reset();
+        this
+    }
+
+    pub fn is_procedural(&self) -> bool {
+        self.generator.is_some()
+    }
+
+    /// Brings a procedural road back to its first stretch. Fixed roads are
+    /// left as they are.
+    pub fn reset(&mut self) {
+        let Some(generator) = &mut self.generator else {
+            return;
+        };
+
+        *generator = RoadGenerator::new(self.x, generator.seed);
+        self.centerline = vec![generator.position];
+        self.headings = vec![generator.heading];
+        self.stations = vec![generator.station];
+        self.extend_to(3_000.0);
+    }
+
+    /// Generates a procedural road up to `station`.
+    pub fn extend_to(&mut self, station: f64) {
+        let Some(generator) = &mut self.generator else {
+            return;
+        };
+
+        if generator.station >= station {
+            return;
+        }
+
+        while generator.station < station {
+            let (point, heading, station) = generator.next();
+            self.centerline.push(point);
+            self.headings.push(heading);
+            self.stations.push(station);
         }
+        self.update_borders();
     }
 
-    pub fn get_late_center(&self, lane_index: usize) -> f64 {
-        let lane_width = self.width / self.lane_count as f64;
-        self.left + lane_width / 2.0 + lane_index.min(self.lane_count - 1) as f64 * lane_width
+    /// Drops the part of a procedural road that lies wholly before `station`.
+    pub fn trim_before(&mut self, station: f64) {
+        if self.generator.is_none() {
+            return;
+        }
+
+        let count = self.stations.partition_point(|&s| s < station);
+        let count = count.saturating_sub(1).min(self.stations.len() - 2);
+        if count > 0 {
+            self.centerline.drain(..count);
+            self.headings.drain(..count);
+            self.stations.drain(..count);
+            self.update_borders();
+        }
     }
 
-    pub fn draw(&self, ctx: &CanvasRenderingContext2d) {
-        ctx.set_line_width(5.0);
-        ctx.set_stroke_style(&JsValue::from_str("white"));
+    pub fn lane_width(&self) -> f64 {
+        self.width / self.lane_count as f64
+    }
 
-        for i in 1..=self.lane_count - 1 {
-            let x = lerp(self.left, self.right, i as f64 / self.lane_count as f64);
+    /// Distance from the centerline to the center of a lane, positive to the
+    /// right.
+    pub fn lane_offset(&self, lane_index: usize) -> f64 {
+        -self.width / 2.0
+            + self.lane_width() / 2.0
+            + lane_index.min(self.lane_count - 1) as f64 * self.lane_width()
+    }
+
+    /// Lane whose center is closest to `lateral`, as returned by [`Road::project`].
+    pub fn nearest_lane(&self, lateral: f64) -> usize {
+        let lane = ((lateral + self.width / 2.0) / self.lane_width()).floor();
+        lane.clamp(0.0, (self.lane_count - 1) as f64) as usize
+    }
 
-            let array = Array::new();
-            array.push(&JsValue::from(20));
-            array.push(&JsValue::from(20));
+    /// Centerline point and heading at `station`.
+    pub fn point_at(&self, station: f64) -> (Coord, f64) {
+        let i = self
+            .stations
+            .partition_point(|&s| s <= station)
+            .clamp(1, self.stations.len() - 1);
+        let (s0, s1) = (self.stations[i - 1], self.stations[i]);
+        let t = ((station - s0) / (s1 - s0)).clamp(0.0, 1.0);
+        let (a, b) = (self.centerline[i - 1], self.centerline[i]);
 
-            ctx.set_line_dash(&array).unwrap();
-            ctx.begin_path();
-            ctx.move_to(x, self.top);
-            ctx.line_to(x, self.bottom);
-            ctx.stroke();
+        (
+            Coord {
+                x: lerp(a.x, b.x, t),
+                y: lerp(a.y, b.y, t),
+            },
+            lerp(self.headings[i - 1], self.headings[i], t),
+        )
+    }
+
+    pub fn lane_center(&self, lane_index: usize, station: f64) -> Coord {
+        let (point, heading) = self.point_at(station);
+        let right = right_of(heading);
+        let offset = self.lane_offset(lane_index);
+
+        Coord {
+            x: point.x + right.x * offset,
+            y: point.y + right.y * offset,
         }
+    }
 
-        ctx.set_line_dash(&Array::new()).unwrap();
-        for border in &self.borders {
-            ctx.begin_path();
-            ctx.move_to(border[0].x, border[0].y);
-            ctx.line_to(border[1].x, border[1].y);
-            ctx.stroke();
+    /// Station of the centerline point closest to `point`, and how far
+    /// `point` is to the right of it.
+    pub fn project(&self, point: Coord) -> (f64, f64) {
+        let mut best = (f64::INFINITY, 0.0, 0.0);
+
+        for i in 0..self.centerline.len() - 1 {
+            let (a, b) = (self.centerline[i], self.centerline[i + 1]);
+            let (dx, dy) = (b.x - a.x, b.y - a.y);
+            let length = dx.hypot(dy);
+            let t =
+                (((point.x - a.x) * dx + (point.y - a.y) * dy) / (length * length)).clamp(0.0, 1.0);
+            let closest = Coord {
+                x: a.x + dx * t,
+                y: a.y + dy * t,
+            };
+            let distance = (point.x - closest.x).hypot(point.y - closest.y);
+
+            if distance < best.0 {
+                let right = Coord {
+                    x: -dy / length,
+                    y: dx / length,
+                };
+                let lateral = (point.x - closest.x) * right.x + (point.y - closest.y) * right.y;
+                best = (distance, self.stations[i] + length * t, lateral);
+            }
         }
+
+        (best.1, best.2)
+    }
+
+    /// The centerline shifted sideways by `offset`, positive to the right.
+    pub fn offset_line(&self, offset: f64) -> Vec<Coord> {
+        self.centerline
+            .iter()
+            .zip(&self.headings)
+            .map(|(point, &heading)| {
+                let right = right_of(heading);
+                Coord {
+                    x: point.x + right.x * offset,
+                    y: point.y + right.y * offset,
+                }
+            })
+            .collect()
+    }
+
+    fn update_borders(&mut self) {
+        self.borders = vec![
+            self.offset_line(-self.width / 2.0),
+            self.offset_line(self.width / 2.0),
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Road;
+    use crate::utils::Coord;
+
+    #[test]
+    fn test_straight_road_stations_follow_y() {
+        let road = Road::new(100.0, 180.0, None);
+
+        let center = road.lane_center(1, 500.0);
+        assert_eq!((center.x, center.y), (100.0, -500.0));
+        assert_eq!(road.lane_center(0, 0.0).x, 40.0);
+
+        let (station, lateral) = road.project(Coord { x: 130.0, y: -20.0 });
+        assert!((station - 20.0).abs() < 1e-6);
+        assert_eq!(lateral, 30.0);
+    }
+
+    #[test]
+    fn test_procedural_road_is_seeded_and_endless() {
+        let mut a = Road::procedural(100.0, 180.0, None, 3);
+        let mut b = Road::procedural(100.0, 180.0, None, 3);
+        a.extend_to(20_000.0);
+        b.extend_to(20_000.0);
+
+        assert_eq!(a.centerline.len(), b.centerline.len());
+        assert!(a
+            .centerline
+            .iter()
+            .zip(&b.centerline)
+            .all(|(a, b)| a.x == b.x && a.y == b.y));
+        assert!(a.headings.iter().any(|heading| heading.abs() > 0.1));
+
+        a.trim_before(10_010.0);
+        assert!(a.stations[0] <= 10_010.0 && a.stations[1] > 10_010.0);
+
+        let point = a.lane_center(2, 15_000.0);
+        let (station, lateral) = a.project(point);
+        assert!((station - 15_000.0).abs() < 1.0);
+        assert!((lateral - a.lane_offset(2)).abs() < 1.0);
     }
 }
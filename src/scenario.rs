@@ -0,0 +1,267 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{controls::ControlKind, road::Road, utils::Coord};
+
+/// Shape of the road's centerline.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoadGeometry {
+    /// Straight up the screen, as in the tutorial.
+    #[default]
+    Straight,
+    /// Endless and curvy, drawn from `seed` or, without one, from the run's seed.
+    Procedural {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    /// Through the given `(x, y)` points, in driving order.
+    Polyline { points: Vec<(f64, f64)> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoadSpec {
+    /// Where the road starts across the screen.
+    pub x: f64,
+    pub width: f64,
+    pub lanes: usize,
+    pub geometry: RoadGeometry,
+}
+
+impl Default for RoadSpec {
+    fn default() -> Self {
+        // The web UI's 200px wide car canvas.
+        Self {
+            x: 100.0,
+            width: 180.0,
+            lanes: 3,
+            geometry: RoadGeometry::Straight,
+        }
+    }
+}
+
+impl RoadSpec {
+    pub fn build(&self, seed: u64) -> Road {
+        match &self.geometry {
+            RoadGeometry::Straight => Road::new(self.x, self.width, Some(self.lanes)),
+            RoadGeometry::Procedural { seed: own_seed } => Road::procedural(
+                self.x,
+                self.width,
+                Some(self.lanes),
+                own_seed.unwrap_or(seed),
+            ),
+            RoadGeometry::Polyline { points } => Road::from_centerline(
+                points.iter().map(|&(x, y)| Coord { x, y }).collect(),
+                self.width,
+                Some(self.lanes),
+            ),
+        }
+    }
+}
+
+/// One traffic car, present from the start of every generation. Positions
+/// are given as `y` on a straight road; on other roads the car starts at the
+/// same distance along the road.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrafficEntry {
+    pub lane: usize,
+    pub y: f64,
+    #[serde(default = "TrafficEntry::default_speed")]
+    pub speed: f64,
+    /// A CSS color; random when left out.
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default = "TrafficEntry::default_control")]
+    pub control: ControlKind,
+}
+
+impl TrafficEntry {
+    fn default_speed() -> f64 {
+        2.0
+    }
+
+    fn default_control() -> ControlKind {
+        ControlKind::Dummy
+    }
+}
+
+/// Where the AI cars start.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Spawn {
+    pub lane: usize,
+    pub y: f64,
+}
+
+impl Default for Spawn {
+    fn default() -> Self {
+        Self { lane: 1, y: 100.0 }
+    }
+}
+
+/// A shareable test case: the road, the traffic on it and the population
+/// that learns to drive it. The default is the tutorial's setup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub name: String,
+    pub road: RoadSpec,
+    pub traffic: Vec<TrafficEntry>,
+    pub spawn: Spawn,
+    pub population: usize,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let traffic = [
+            (1, -100.0),
+            (0, -300.0),
+            (2, -300.0),
+            (0, -500.0),
+            (1, -500.0),
+            (1, -700.0),
+            (2, -700.0),
+        ]
+        .into_iter()
+        .map(|(lane, y)| TrafficEntry {
+            lane,
+            y,
+            speed: TrafficEntry::default_speed(),
+            color: None,
+            control: TrafficEntry::default_control(),
+        })
+        .collect();
+
+        Self {
+            name: "tutorial".to_string(),
+            road: RoadSpec::default(),
+            traffic,
+            spawn: Spawn::default(),
+            population: 100,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Json(serde_json::Error),
+    Ron(ron::error::SpannedError),
+    /// Well-formed, but describes something that cannot be built.
+    Invalid(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "invalid JSON scenario: {err}"),
+            Self::Ron(err) => write!(f, "invalid RON scenario: {err}"),
+            Self::Invalid(reason) => write!(f, "invalid scenario: {reason}"),
+        }
+    }
+}
+
+impl Error for ScenarioError {}
+
+impl Scenario {
+    /// Reads a scenario written as JSON or as RON. JSON is recognised by its
+    /// opening `{`.
+    pub fn parse(text: &str) -> Result<Self, ScenarioError> {
+        let scenario: Self = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(ScenarioError::Json)?
+        } else {
+            ron::from_str(text).map_err(ScenarioError::Ron)?
+        };
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        let invalid = |reason: String| Err(ScenarioError::Invalid(reason));
+
+        if self.road.lanes == 0 || self.road.width <= 0.0 {
+            return invalid("the road needs at least one lane and a positive width".to_string());
+        }
+        if let RoadGeometry::Polyline { points } = &self.road.geometry {
+            if points.len() < 2 {
+                return invalid("a polyline road needs at least two points".to_string());
+            }
+        }
+        if let Some(entry) = self
+            .traffic
+            .iter()
+            .find(|entry| entry.lane >= self.road.lanes)
+        {
+            return invalid(format!("traffic lane {} is not on the road", entry.lane));
+        }
+        // Keyboard controls need a browser and AI traffic has no brain.
+        if let Some(entry) = self
+            .traffic
+            .iter()
+            .find(|entry| !matches!(entry.control, ControlKind::Dummy | ControlKind::Agent))
+        {
+            return invalid(format!(
+                "traffic control {} is not dummy or agent",
+                format!("{:?}", entry.control).to_lowercase()
+            ));
+        }
+        if self.spawn.lane >= self.road.lanes {
+            return invalid(format!("spawn lane {} is not on the road", self.spawn.lane));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RoadGeometry, Scenario, ScenarioError};
+    use crate::controls::ControlKind;
+
+    #[test]
+    fn test_scenario_round_trips_json_and_ron() {
+        let scenario = Scenario::default();
+        assert_eq!(Scenario::parse(&scenario.to_json()).unwrap(), scenario);
+        assert_eq!(Scenario::parse(&scenario.to_ron()).unwrap(), scenario);
+    }
+
+    #[test]
+    fn test_shipped_scenarios_parse() {
+        let tutorial = Scenario::parse(include_str!("../scenarios/tutorial.ron")).unwrap();
+        assert_eq!(tutorial, Scenario::default());
+
+        let merge = Scenario::parse(include_str!("../scenarios/merge.json")).unwrap();
+        assert_eq!(
+            merge.road.geometry,
+            RoadGeometry::Procedural { seed: Some(1) }
+        );
+        assert!(merge
+            .traffic
+            .iter()
+            .any(|t| t.control == ControlKind::Agent));
+        assert!(Scenario::parse("(road: (lanes: \"three\"))").is_err());
+        assert!(Scenario::parse("(road: (lanes: 2), spawn: (lane: 2))").is_err());
+    }
+
+    #[test]
+    fn test_traffic_must_drive_itself() {
+        for control in ["keys", "ai"] {
+            let json =
+                format!(r#"{{"traffic": [{{"lane": 0, "y": 0.0, "control": "{control}"}}]}}"#);
+            assert!(
+                matches!(Scenario::parse(&json), Err(ScenarioError::Invalid(_))),
+                "{control} traffic was accepted"
+            );
+        }
+        let agent = r#"{"traffic": [{"lane": 0, "y": 0.0, "control": "agent"}]}"#;
+        assert!(Scenario::parse(agent).is_ok());
+    }
+}
@@ -1,89 +1,305 @@
-use wasm_bindgen::JsValue;
-use web_sys::CanvasRenderingContext2d;
+use std::{f64::consts::PI, str::FromStr};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    car::{Car, CarPtr},
+    car::Car,
+    spatial::{Obstacle, Obstacles},
     utils::{get_intersection, lerp, Coord, CoordWithOffset},
 };
 
-#[repr(C)]
-#[derive(Debug, Clone, Default)]
-pub struct Sensor {
+/// Inputs a sensor adds on top of each ray's distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Modalities {
+    /// Per ray: 1 when it hit a car, 0 for a border or nothing.
+    pub hit_kind: bool,
+    /// Per ray: how fast the car closes in on what the ray hit, in top speeds.
+    pub closing_speed: bool,
+    /// The car's own speed, in top speeds.
+    pub speed: bool,
+    /// The car's heading relative to the road, in half turns; positive is left.
+    pub heading: bool,
+    /// Distance to the nearest lane center, in lane widths; positive is right.
+    pub lane_offset: bool,
+}
+
+impl Modalities {
+    pub const NAMES: [&'static str; 5] = [
+        "hit-kind",
+        "closing-speed",
+        "speed",
+        "heading",
+        "lane-offset",
+    ];
+
+    pub fn all() -> Self {
+        Self::from_bits(u8::MAX)
+    }
+
+    /// One bit per modality, in [`Modalities::NAMES`] order.
+    pub fn to_bits(self) -> u8 {
+        [
+            self.hit_kind,
+            self.closing_speed,
+            self.speed,
+            self.heading,
+            self.lane_offset,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &flag)| u8::from(flag) << i)
+        .sum()
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let flag = |i: usize| bits & (1 << i) != 0;
+        Self {
+            hit_kind: flag(0),
+            closing_speed: flag(1),
+            speed: flag(2),
+            heading: flag(3),
+            lane_offset: flag(4),
+        }
+    }
+}
+
+impl FromStr for Modalities {
+    type Err = String;
+
+    /// A comma separated list of [`Modalities::NAMES`], or `none`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bits = 0;
+        for name in s.split(',').filter(|&name| name != "none") {
+            let i = Self::NAMES
+                .iter()
+                .position(|&known| known == name)
+                .ok_or_else(|| format!("unknown sensor input `{name}`"))?;
+            bits |= 1 << i;
+        }
+        Ok(Self::from_bits(bits))
+    }
+}
+
+/// How a sensor's rays are laid out and what it reports. A brain only drives
+/// well with the sensor it was trained on, so it is stored with the brain and
+/// its input count follows [`SensorConfig::input_count`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorConfig {
     pub ray_count: usize,
     pub ray_length: usize,
+    /// Angle between the outermost rays, in radians. At a full turn the rays
+    /// are spread evenly all around.
     pub ray_spread: f64,
+    /// Direction the rays fan out around, in radians from straight ahead;
+    /// positive turns left, so PI faces backwards.
+    pub ray_offset: f64,
+    /// Where the rays start, as `(right, forward)` from the car's center.
+    pub mount: (f64, f64),
+    pub modalities: Modalities,
+}
+
+impl SensorConfig {
+    /// What each input of a brain with this sensor reads, in order: every
+    /// ray's distance followed by its per ray modalities, then the car's own.
+    pub fn input_names(&self) -> Vec<String> {
+        let modalities = self.modalities;
+        let mut names = Vec::new();
+        for i in 0..self.ray_count {
+            names.push(format!("ray {i}"));
+            if modalities.hit_kind {
+                names.push(format!("ray {i} hit kind"));
+            }
+            if modalities.closing_speed {
+                names.push(format!("ray {i} closing speed"));
+            }
+        }
+        for (name, enabled) in [
+            ("speed", modalities.speed),
+            ("heading", modalities.heading),
+            ("lane offset", modalities.lane_offset),
+        ] {
+            if enabled {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
+    /// The length of [`SensorConfig::input_names`], without building them, so
+    /// a corrupted ray count costs nothing.
+    pub fn input_count(&self) -> usize {
+        let modalities = self.modalities;
+        let per_ray = 1 + usize::from(modalities.hit_kind) + usize::from(modalities.closing_speed);
+        self.ray_count.saturating_mul(per_ray)
+            + usize::from(modalities.speed)
+            + usize::from(modalities.heading)
+            + usize::from(modalities.lane_offset)
+    }
+}
+
+impl Default for SensorConfig {
+    fn default() -> Self {
+        Self {
+            ray_count: 5,
+            ray_length: 150,
+            ray_spread: PI / 2.0,
+            ray_offset: 0.0,
+            mount: (0.0, 0.0),
+            modalities: Modalities::default(),
+        }
+    }
+}
+
+/// Named sensor layouts offered by the web UI and the trainer.
+pub fn built_in() -> Vec<(&'static str, SensorConfig)> {
+    let default = SensorConfig::default();
+    vec![
+        ("front", default),
+        (
+            "wide",
+            SensorConfig {
+                ray_count: 9,
+                ray_spread: PI,
+                ..default
+            },
+        ),
+        (
+            "360",
+            SensorConfig {
+                ray_count: 16,
+                ray_spread: 2.0 * PI,
+                ..default
+            },
+        ),
+        (
+            "aware",
+            SensorConfig {
+                modalities: Modalities::all(),
+                ..default
+            },
+        ),
+        (
+            "rear",
+            SensorConfig {
+                ray_offset: PI,
+                mount: (0.0, -25.0),
+                ..default
+            },
+        ),
+    ]
+}
+
+pub fn from_name(name: &str) -> Option<SensorConfig> {
+    built_in()
+        .into_iter()
+        .find(|&(preset, _)| preset == name)
+        .map(|(_, config)| config)
+}
+
+/// Name of the built-in layout `config` matches, if any.
+pub fn name_of(config: &SensorConfig) -> Option<&'static str> {
+    built_in()
+        .into_iter()
+        .find(|(_, preset)| preset == config)
+        .map(|(name, _)| name)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct Sensor {
+    pub config: SensorConfig,
     pub rays: Vec<(Coord, Coord)>,
     pub readings: Vec<Option<CoordWithOffset>>,
+    /// Index into [`Obstacles::bodies`] of the car each ray hit, if it hit one.
+    pub hits: Vec<Option<usize>>,
+    /// What the brain is fed, laid out as [`SensorConfig::input_names`].
+    pub inputs: Vec<f64>,
 }
 
 impl Sensor {
-    pub fn new() -> Self {
+    pub fn new(config: SensorConfig) -> Self {
         Self {
-            ray_count: 5,
-            ray_length: 150,
-            ray_spread: std::f64::consts::PI / 2.0,
+            config,
             rays: Vec::new(),
             readings: Vec::new(),
+            hits: Vec::new(),
+            inputs: Vec::new(),
         }
     }
 
-    pub fn update(&mut self, car: &Car, road_borders: &[Vec<Coord>], traffic: &[CarPtr]) {
+    /// Casts the rays and computes the inputs. Heading and lane offset are
+    /// taken from the car's stats, as recorded after the last tick.
+    pub fn update(&mut self, car: &Car, obstacles: &Obstacles) {
         self.cast_rays(car);
         self.readings = Vec::new();
+        self.hits = Vec::new();
         for i in 0..self.rays.len() {
-            self.readings
-                .push(Self::get_reading(self.rays[i], road_borders, traffic));
+            let (reading, hit) = Self::get_reading(self.rays[i], obstacles)
+                .map_or((None, None), |(reading, hit)| (Some(reading), hit));
+            self.readings.push(reading);
+            self.hits.push(hit);
         }
-    }
 
-    pub fn draw(&self, ctx: &CanvasRenderingContext2d) {
-        for i in 0..self.ray_count {
-            let mut end = CoordWithOffset {
-                x: self.rays[i].1.x,
-                y: self.rays[i].1.y,
-                ..Default::default()
-            };
-            if let Some(reading) = self.readings[i] {
-                end = reading
-            }
+        let modalities = self.config.modalities;
 
-            ctx.begin_path();
-            ctx.set_line_width(2.0);
-            ctx.set_stroke_style(&JsValue::from_str("yellow"));
-            ctx.move_to(self.rays[i].0.x, self.rays[i].0.y);
-            ctx.line_to(end.x, end.y);
-            ctx.stroke();
-            ctx.begin_path();
-            ctx.set_line_width(2.0);
-            ctx.set_stroke_style(&JsValue::from_str("black"));
-            ctx.move_to(self.rays[i].1.x, self.rays[i].1.y);
-            ctx.line_to(end.x, end.y);
-            ctx.stroke();
+        self.inputs = Vec::new();
+        for (i, &(start, end)) in self.rays.iter().enumerate() {
+            let reading = self.readings[i];
+            self.inputs
+                .push(reading.map_or(0.0, |reading| 1.0 - reading.offset));
+            if modalities.hit_kind {
+                self.inputs
+                    .push(if self.hits[i].is_some() { 1.0 } else { 0.0 });
+            }
+            if modalities.closing_speed {
+                // Borders stand still.
+                let other = self.hits[i]
+                    .map_or(Coord { x: 0.0, y: 0.0 }, |j| obstacles.bodies[j].velocity());
+                let own = car.velocity();
+                let length = self.config.ray_length as f64;
+                let closing_speed = ((own.x - other.x) * (end.x - start.x)
+                    + (own.y - other.y) * (end.y - start.y))
+                    / length
+                    / car.max_speed;
+                self.inputs.push(reading.map_or(0.0, |_| closing_speed));
+            }
+        }
+        if modalities.speed {
+            self.inputs.push(car.speed / car.max_speed);
+        }
+        if modalities.heading {
+            self.inputs.push(car.stats.heading / PI);
+        }
+        if modalities.lane_offset {
+            self.inputs.push(car.stats.lane_deviation);
         }
     }
 
     fn get_reading(
         ray: (Coord, Coord),
-        road_borders: &[Vec<Coord>],
-        traffic: &[CarPtr],
-    ) -> Option<CoordWithOffset> {
+        obstacles: &Obstacles,
+    ) -> Option<(CoordWithOffset, Option<usize>)> {
         let mut touches = Vec::new();
 
-        for border in road_borders {
-            let touch = get_intersection(ray.0, ray.1, border[0], border[1]);
-
-            if let Some(touch) = touch {
-                touches.push(touch);
-            }
-        }
-
-        for car in traffic {
-            let poly = &car.polygon;
+        for obstacle in obstacles.near(ray.0, ray.1) {
+            match obstacle {
+                Obstacle::Segment(a, b) => {
+                    if let Some(touch) = get_intersection(ray.0, ray.1, a, b) {
+                        touches.push((touch, None));
+                    }
+                }
+                Obstacle::Car(i) => {
+                    let poly = &obstacles.bodies[i].polygon;
 
-            for j in 0..poly.len() {
-                let value = get_intersection(ray.0, ray.1, poly[j], poly[(j + 1) % poly.len()]);
-                if let Some(value) = value {
-                    touches.push(value);
+                    for j in 0..poly.len() {
+                        let value =
+                            get_intersection(ray.0, ray.1, poly[j], poly[(j + 1) % poly.len()]);
+                        if let Some(value) = value {
+                            touches.push((value, Some(i)));
+                        }
+                    }
                 }
             }
         }
@@ -91,37 +307,143 @@ impl Sensor {
         if touches.is_empty() {
             None
         } else {
-            let offsets = touches.iter().map(|e| e.offset).collect::<Vec<f64>>();
+            let offsets = touches.iter().map(|e| e.0.offset).collect::<Vec<f64>>();
             let min_offset = offsets
                 .iter()
                 .min_by(|x, y| x.partial_cmp(y).unwrap())
                 .copied()
                 .unwrap();
-            touches.iter().find(|e| e.offset == min_offset).copied()
+            touches.iter().find(|e| e.0.offset == min_offset).copied()
         }
     }
 
     fn cast_rays(&mut self, car: &Car) {
-        self.rays = Vec::new();
+        let config = &self.config;
+        // A full turn would put the first and last rays on top of each other.
+        let steps = if config.ray_spread >= 2.0 * PI - 1e-9 {
+            config.ray_count
+        } else {
+            config.ray_count.saturating_sub(1)
+        };
+        let (right, forward) = config.mount;
+        let start = Coord {
+            x: car.x + car.angle.cos() * right - car.angle.sin() * forward,
+            y: car.y - car.angle.sin() * right - car.angle.cos() * forward,
+        };
 
-        for i in 0..self.ray_count {
+        self.rays = Vec::new();
+        for i in 0..config.ray_count {
             let ray_angle = lerp(
-                self.ray_spread / 2.0,
-                -self.ray_spread / 2.0,
-                if self.ray_count == 1 {
+                config.ray_spread / 2.0,
+                -config.ray_spread / 2.0,
+                if steps == 0 {
                     0.5
                 } else {
-                    i as f64 / (self.ray_count - 1) as f64
+                    i as f64 / steps as f64
                 },
-            ) + car.angle;
+            ) + config.ray_offset
+                + car.angle;
 
-            let start = Coord { x: car.x, y: car.y };
             let end = Coord {
-                x: (car.x - ray_angle.sin() * self.ray_length as f64),
-                y: (car.y - ray_angle.cos() * self.ray_length as f64),
+                x: (start.x - ray_angle.sin() * config.ray_length as f64),
+                y: (start.y - ray_angle.cos() * config.ray_length as f64),
             };
 
             self.rays.push((start, end));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{from_name, Modalities, SensorConfig};
+    use crate::{
+        car::Car, controls::ControlKind, network::BrainConfig, road::Road, spatial::Obstacles,
+        world::World,
+    };
+
+    fn car_with(sensor: SensorConfig) -> Car {
+        let brain = BrainConfig {
+            sensor,
+            ..Default::default()
+        }
+        .build(&mut StdRng::seed_from_u64(0));
+        Car::new(
+            100.0,
+            100.0,
+            30.0,
+            50.0,
+            ControlKind::AI,
+            None,
+            None,
+            Some(brain),
+        )
+    }
+
+    #[test]
+    fn test_brain_inputs_follow_sensor() {
+        let road = Road::new(100.0, 180.0, None);
+
+        let mut all_around = car_with(from_name("360").unwrap());
+        assert_eq!(all_around.brain.as_ref().unwrap().shape()[0], 16);
+        all_around.update(&Obstacles::new(&road.borders, &[]));
+        let rays = &all_around.sensor.as_ref().unwrap().rays;
+        assert_eq!(rays.len(), 16);
+        // Spread evenly, so the first and last rays do not overlap.
+        assert!((rays[0].1.x - rays[15].1.x).abs() > 1.0);
+
+        let mut rear = car_with(from_name("rear").unwrap());
+        rear.update(&Obstacles::new(&road.borders, &[]));
+        let (start, end) = rear.sensor.as_ref().unwrap().rays[2];
+        assert!((start.y - 125.0).abs() < 1e-9);
+        assert!((end.y - start.y - 150.0).abs() < 1e-9);
+
+        let sideways = SensorConfig {
+            ray_count: 1,
+            ray_offset: PI / 2.0,
+            ..Default::default()
+        };
+        let mut left = car_with(sideways);
+        left.update(&Obstacles::new(&road.borders, &[]));
+        let reading = left.sensor.as_ref().unwrap().readings[0].unwrap();
+        // The road's left border runs along x = 10.
+        assert!((reading.x - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_modalities_tell_cars_from_borders() {
+        let road = Road::new(100.0, 180.0, None);
+        let sensor = SensorConfig {
+            ray_count: 3,
+            ray_spread: PI,
+            modalities: "hit-kind,closing-speed,speed"
+                .parse::<Modalities>()
+                .unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(sensor.input_count(), 3 * 3 + 1);
+        assert_eq!(sensor.input_count(), sensor.input_names().len());
+
+        let mut car = car_with(sensor);
+        World::place(&mut car, &road, 1, 0.0);
+        car.speed = 2.0;
+        let mut ahead = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::Dummy, None, None, None);
+        World::place(&mut ahead, &road, 1, 100.0);
+        ahead.update(&Obstacles::new(&road.borders, &[]));
+
+        let ahead_speed = ahead.speed;
+        car.update(&Obstacles::new(&road.borders, &[ahead]));
+        let inputs = &car.sensor.as_ref().unwrap().inputs;
+        assert_eq!(inputs.len(), 10);
+        // Left ray: the border, which the car drives alongside.
+        assert!(inputs[0] > 0.0 && inputs[1] == 0.0 && inputs[2].abs() < 1e-9);
+        // Middle ray: the slower car ahead.
+        assert!(inputs[3] > 0.0 && inputs[4] == 1.0);
+        assert!((inputs[5] - (car.speed - ahead_speed) / car.max_speed).abs() < 1e-9);
+        assert_eq!(inputs[9], car.speed / car.max_speed);
+    }
+}
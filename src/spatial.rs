@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use crate::{
+    car::{Car, CarPtr},
+    utils::{boxes_overlap, Coord},
+};
+
+/// Side of a grid cell. About a sensor ray's length, so a ray or a car
+/// only looks at a handful of cells.
+pub const CELL_SIZE: f64 = 100.0;
+
+/// Items spanning more cells than this, such as the two borders of a
+/// straight road, are kept aside and returned by every query instead.
+const MAX_CELLS: i64 = 64;
+
+/// A uniform grid over axis-aligned bounding boxes, answering which items
+/// may overlap a box. Items are numbered in insertion order.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    boxes: Vec<(Coord, Coord)>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    oversized: Vec<usize>,
+}
+
+impl SpatialGrid {
+    /// Adds the box spanned by `a` and `b` and returns its number.
+    pub fn insert(&mut self, a: Coord, b: Coord) -> usize {
+        let item = self.boxes.len();
+        self.boxes.push((a, b));
+
+        let ((x0, y0), (x1, y1)) = Self::cell_range(a, b);
+        if (x1 - x0 + 1) * (y1 - y0 + 1) > MAX_CELLS {
+            self.oversized.push(item);
+            return item;
+        }
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(item);
+            }
+        }
+        item
+    }
+
+    /// Every item whose box overlaps the one spanned by `a` and `b`, in
+    /// insertion order.
+    pub fn query(&self, a: Coord, b: Coord) -> Vec<usize> {
+        let ((x0, y0), (x1, y1)) = Self::cell_range(a, b);
+        let mut items = self.oversized.clone();
+        if (x1 - x0 + 1) * (y1 - y0 + 1) > MAX_CELLS {
+            items.extend(0..self.boxes.len());
+        } else {
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    items.extend(self.cells.get(&(x, y)).into_iter().flatten());
+                }
+            }
+        }
+
+        items.sort_unstable();
+        items.dedup();
+        items.retain(|&item| {
+            let (c, d) = self.boxes[item];
+            boxes_overlap(a, b, c, d)
+        });
+        items
+    }
+
+    fn cell_range(a: Coord, b: Coord) -> ((i64, i64), (i64, i64)) {
+        let cell = |v: f64| (v / CELL_SIZE).floor() as i64;
+        (
+            (cell(a.x.min(b.x)), cell(a.y.min(b.y))),
+            (cell(a.x.max(b.x)), cell(a.y.max(b.y))),
+        )
+    }
+}
+
+/// What a car can hit or sense of another car.
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub polygon: Vec<Coord>,
+    pub angle: f64,
+    pub speed: f64,
+    /// Index into the population, for AI cars.
+    pub member: Option<usize>,
+}
+
+impl Body {
+    pub fn of(car: &Car, member: Option<usize>) -> Self {
+        Self {
+            polygon: car.polygon.clone(),
+            angle: car.angle,
+            speed: car.speed,
+            member,
+        }
+    }
+
+    pub fn velocity(&self) -> Coord {
+        Coord {
+            x: -self.angle.sin() * self.speed,
+            y: -self.angle.cos() * self.speed,
+        }
+    }
+}
+
+/// The road borders and cars that can be hit or sensed during one tick,
+/// indexed so each query only looks at what is nearby.
+#[derive(Debug, Clone, Default)]
+pub struct Obstacles {
+    /// Traffic first, in order, then any population members.
+    pub bodies: Vec<Body>,
+    /// Population member being updated, left out of every query so it does
+    /// not run into itself.
+    pub member: Option<usize>,
+    grid: SpatialGrid,
+    /// What each grid item is: a border segment, or a body.
+    items: Vec<Obstacle>,
+}
+
+/// Something a car can hit or sense.
+#[derive(Debug, Clone, Copy)]
+pub enum Obstacle {
+    Segment(Coord, Coord),
+    /// Index into [`Obstacles::bodies`].
+    Car(usize),
+}
+
+impl Obstacles {
+    pub fn new(road_borders: &[Vec<Coord>], traffic: &[CarPtr]) -> Self {
+        let mut this = Self::default();
+        for border in road_borders {
+            for segment in border.windows(2) {
+                this.grid.insert(segment[0], segment[1]);
+                this.items.push(Obstacle::Segment(segment[0], segment[1]));
+            }
+        }
+        this.add_traffic(traffic);
+        this
+    }
+
+    /// Adds traffic cars, after whatever is already there.
+    pub fn add_traffic(&mut self, traffic: &[CarPtr]) {
+        for car in traffic {
+            self.add(Body::of(car, None));
+        }
+    }
+
+    /// Adds the AI cars, so they can hit and sense each other. Wrecks stay
+    /// on the road, so a car that was run into is damaged too, but
+    /// eliminated cars are gone.
+    pub fn add_population(&mut self, cars: &[CarPtr]) {
+        for (i, car) in cars.iter().enumerate() {
+            if !car.eliminated {
+                self.add(Body::of(car, Some(i)));
+            }
+        }
+    }
+
+    fn add(&mut self, body: Body) {
+        if let Some((min, max)) = bounds(&body.polygon) {
+            self.grid.insert(min, max);
+            self.items.push(Obstacle::Car(self.bodies.len()));
+        }
+        self.bodies.push(body);
+    }
+
+    /// Border segments first and then bodies, each in order, whose bounding
+    /// boxes overlap the one spanned by `a` and `b`.
+    pub fn near(&self, a: Coord, b: Coord) -> impl Iterator<Item = Obstacle> + '_ {
+        self.grid
+            .query(a, b)
+            .into_iter()
+            .map(|item| self.items[item])
+            .filter(|obstacle| match *obstacle {
+                Obstacle::Car(i) => self.member.is_none() || self.bodies[i].member != self.member,
+                Obstacle::Segment(..) => true,
+            })
+    }
+}
+
+/// The corners of the bounding box of `points`, if there are any.
+pub fn bounds(points: &[Coord]) -> Option<(Coord, Coord)> {
+    let first = *points.first()?;
+    Some(points.iter().fold((first, first), |(min, max), p| {
+        (
+            Coord {
+                x: min.x.min(p.x),
+                y: min.y.min(p.y),
+            },
+            Coord {
+                x: max.x.max(p.x),
+                y: max.y.max(p.y),
+            },
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Obstacle, Obstacles, SpatialGrid};
+    use crate::{car::Car, controls::ControlKind, road::Road, utils::Coord, world::World};
+
+    #[test]
+    fn test_grid_finds_only_nearby_boxes() {
+        let point = |x: f64, y: f64| Coord { x, y };
+        let mut grid = SpatialGrid::default();
+        grid.insert(point(0.0, 0.0), point(10.0, 10.0));
+        grid.insert(point(500.0, 500.0), point(510.0, 510.0));
+        grid.insert(point(5.0, -1e6), point(5.0, 1e6));
+
+        assert_eq!(grid.query(point(-5.0, -5.0), point(5.0, 5.0)), vec![0, 2]);
+        assert_eq!(
+            grid.query(point(505.0, 400.0), point(505.0, 600.0)),
+            vec![1]
+        );
+        assert_eq!(
+            grid.query(point(-1e4, -1e4), point(1e4, 1e4)),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_obstacles_near_a_car() {
+        let road = Road::procedural(100.0, 180.0, None, 3);
+        let traffic = (0..20)
+            .map(|i| {
+                let mut car = Car::new(0.0, 0.0, 30.0, 50.0, ControlKind::Dummy, None, None, None);
+                World::place(&mut car, &road, i % 3, i as f64 * 150.0);
+                car.update(&Obstacles::new(&[], &[]));
+                car
+            })
+            .collect::<Vec<Car>>();
+        let obstacles = Obstacles::new(&road.borders, &traffic);
+
+        let (a, b) = (traffic[10].polygon[0], traffic[10].polygon[2]);
+        let near = obstacles.near(a, b).collect::<Vec<Obstacle>>();
+        assert!(near
+            .iter()
+            .any(|obstacle| matches!(obstacle, Obstacle::Car(10))));
+        assert!(!near
+            .iter()
+            .any(|obstacle| matches!(obstacle, Obstacle::Car(0))));
+        assert!(near.len() < 5);
+    }
+}
@@ -0,0 +1,223 @@
+use std::str::FromStr;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    car::{Car, CarPtr},
+    controls::ControlKind,
+    road::Road,
+    utils::get_random_color,
+    world::World,
+};
+
+/// Which lanes the cars of one row of traffic take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanePattern {
+    /// One car per row, in a random lane.
+    Random,
+    /// Every lane but one random gap is taken, like the tutorial's walls.
+    Gaps,
+}
+
+/// Top speed given to each spawned car.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpeedDistribution {
+    Constant(f64),
+    Uniform { min: f64, max: f64 },
+}
+
+impl SpeedDistribution {
+    pub fn sample(&self, rng: &mut impl Rng) -> f64 {
+        match *self {
+            Self::Constant(speed) => speed,
+            Self::Uniform { min, max } if min < max => rng.gen_range(min..max),
+            Self::Uniform { min, .. } => min,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrafficConfig {
+    /// Average number of cars per 1000 pixels of road.
+    pub density: f64,
+    pub lanes: LanePattern,
+    pub speed: SpeedDistribution,
+    /// `Dummy` cars hold their speed and lane; `Agent`s follow, brake and
+    /// change lanes.
+    pub driver: ControlKind,
+    /// Station of the first row, so the starting grid is left clear.
+    pub start: f64,
+    /// Traffic is spawned this far ahead of the leading car.
+    pub ahead: f64,
+    /// Cars this far behind the last car are removed.
+    pub behind: f64,
+}
+
+impl Default for TrafficConfig {
+    fn default() -> Self {
+        Self {
+            density: 8.0,
+            lanes: LanePattern::Random,
+            speed: SpeedDistribution::Constant(2.0),
+            driver: ControlKind::Dummy,
+            start: 100.0,
+            ahead: 1_000.0,
+            behind: 1_000.0,
+        }
+    }
+}
+
+/// Keeps dummy traffic coming ahead of the population for as long as it
+/// drives. Every generation starts from the same seed, so each one meets the
+/// same traffic.
+#[derive(Debug, Clone)]
+pub struct TrafficSpawner {
+    pub config: TrafficConfig,
+    pub seed: u64,
+    /// Station of every car removed since the last reset, where it was
+    /// when it fell behind.
+    pub removed: Vec<f64>,
+    rng: StdRng,
+    next_station: f64,
+}
+
+impl TrafficSpawner {
+    pub fn new(config: TrafficConfig, seed: u64) -> Self {
+        Self {
+            config,
+            seed,
+            removed: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            next_station: config.start,
+        }
+    }
+
+    /// Starts over from the first row.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config, self.seed);
+    }
+
+    /// Spawns rows up to `ahead` past `leader` and removes the cars left
+    /// `behind` past `trailer`, both given as stations along `road`.
+    pub fn update(&mut self, road: &Road, traffic: &mut Vec<CarPtr>, leader: f64, trailer: f64) {
+        while self.next_station <= leader + self.config.ahead {
+            self.spawn_row(road, traffic);
+        }
+
+        let last = trailer - self.config.behind;
+        self.removed.extend(
+            traffic
+                .iter()
+                .map(|car| car.stats.station)
+                .filter(|&station| station < last),
+        );
+        traffic.retain(|car| car.stats.station >= last);
+    }
+
+    fn spawn_row(&mut self, road: &Road, traffic: &mut Vec<CarPtr>) {
+        let lanes = match self.config.lanes {
+            LanePattern::Random => vec![self.rng.gen_range(0..road.lane_count)],
+            LanePattern::Gaps => {
+                let gap = self.rng.gen_range(0..road.lane_count);
+                (0..road.lane_count).filter(|&lane| lane != gap).collect()
+            }
+        };
+
+        let station = self.next_station;
+        for &lane in &lanes {
+            let speed = self.config.speed.sample(&mut self.rng);
+            let mut car = Car::new(
+                0.0,
+                0.0,
+                30.0,
+                50.0,
+                self.config.driver,
+                Some(speed),
+                Some(&get_random_color(&mut self.rng)),
+                None,
+            );
+            World::place(&mut car, road, lane, station);
+            car.speed = speed;
+            traffic.push(car);
+        }
+
+        // Rows are spaced so the average matches the density, give or take half.
+        let spacing = lanes.len() as f64 * 1_000.0 / self.config.density.max(f64::EPSILON);
+        self.next_station += spacing * self.rng.gen_range(0.5..1.5);
+    }
+}
+
+impl FromStr for LanePattern {
+    type Err = String;
+
+    /// `random` or `gaps`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Self::Random),
+            "gaps" => Ok(Self::Gaps),
+            _ => Err(format!("unknown lane pattern `{s}`")),
+        }
+    }
+}
+
+impl FromStr for SpeedDistribution {
+    type Err = String;
+
+    /// `<speed>` or `<min>:<max>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |field: &str| {
+            field
+                .parse::<f64>()
+                .map_err(|err| format!("`{field}`: {err}"))
+        };
+
+        match s.split_once(':') {
+            Some((min, max)) => Ok(Self::Uniform {
+                min: parse(min)?,
+                max: parse(max)?,
+            }),
+            None => parse(s).map(Self::Constant),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LanePattern, TrafficConfig, TrafficSpawner};
+    use crate::road::Road;
+
+    #[test]
+    fn test_spawner_is_seeded_and_endless() {
+        let road = Road::new(100.0, 180.0, None);
+        let config = TrafficConfig {
+            lanes: LanePattern::Gaps,
+            speed: "1.5:2.5".parse().unwrap(),
+            ..Default::default()
+        };
+        let spawn = |leader: f64| {
+            let mut spawner = TrafficSpawner::new(config, 11);
+            let mut traffic = Vec::new();
+            spawner.update(&road, &mut traffic, leader, 0.0);
+            (spawner, traffic)
+        };
+
+        let (mut spawner, mut traffic) = spawn(5_000.0);
+        let (_, same) = spawn(5_000.0);
+        assert!(traffic.len() > 10);
+        assert!(
+            traffic
+                .iter()
+                .zip(&same)
+                .all(|(a, b)| (a.x, a.y, a.max_speed) == (b.x, b.y, b.max_speed)
+                    && a.color == b.color)
+        );
+        assert!(traffic.iter().all(|car| car.stats.station <= 6_000.0));
+
+        spawner.update(&road, &mut traffic, 10_000.0, 4_000.0);
+        assert!(!spawner.removed.is_empty());
+        assert!(traffic
+            .iter()
+            .all(|car| car.stats.station >= 3_000.0 && car.stats.station <= 11_000.0));
+        assert!(traffic.iter().any(|car| car.stats.station > 10_000.0));
+    }
+}
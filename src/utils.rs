@@ -3,7 +3,7 @@ use std::{
     ops::{Add, Mul, Sub},
 };
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
@@ -57,28 +57,46 @@ pub fn get_rgba(value: f64) -> String {
     format!("rgba({r},{g},{b},{alpha})")
 }
 
-pub fn get_random_color() -> String {
-    let mut rng = thread_rng();
+pub fn get_random_color(rng: &mut impl Rng) -> String {
     let hue = 290 + rng.gen_range(0..260);
     format!("hsl({hue}, 100%, 60%)")
 }
 
-pub fn polys_intersect(poly1: &[Coord], poly2: &[Coord]) -> bool {
-    for i in 0..poly1.len() {
-        for j in 0..poly2.len() {
-            let touch = get_intersection(
-                poly1[i],
-                poly1[(i + 1) % poly1.len()],
-                poly2[j],
-                poly2[(j + 1) % poly2.len()],
-            );
-
-            if touch.is_some() {
-                return true;
+/// Where the outline of `poly` crosses `edges`, each point with the edge it
+/// lies on. Empty if the two do not touch.
+pub fn poly_contacts(
+    poly: &[Coord],
+    edges: impl IntoIterator<Item = (Coord, Coord)>,
+) -> Vec<(Coord, (Coord, Coord))> {
+    let mut contacts = Vec::new();
+    for (c, d) in edges {
+        for i in 0..poly.len() {
+            if let Some(touch) = get_intersection(poly[i], poly[(i + 1) % poly.len()], c, d) {
+                contacts.push((
+                    Coord {
+                        x: touch.x,
+                        y: touch.y,
+                    },
+                    (c, d),
+                ));
             }
         }
     }
-    false
+    contacts
+}
+
+/// The sides of the closed polygon `poly`.
+pub fn poly_edges(poly: &[Coord]) -> impl Iterator<Item = (Coord, Coord)> + '_ {
+    (0..poly.len()).map(|i| (poly[i], poly[(i + 1) % poly.len()]))
+}
+
+/// Whether the bounding boxes of segments `a`-`b` and `c`-`d` overlap, a cheap
+/// test to skip most segments before [`get_intersection`].
+pub fn boxes_overlap(a: Coord, b: Coord, c: Coord, d: Coord) -> bool {
+    a.x.min(b.x) <= c.x.max(d.x)
+        && c.x.min(d.x) <= a.x.max(b.x)
+        && a.y.min(b.y) <= c.y.max(d.y)
+        && c.y.min(d.y) <= a.y.max(b.y)
 }
 
 #[cfg(test)]
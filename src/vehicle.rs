@@ -0,0 +1,178 @@
+use std::{fmt::Debug, ops::Deref, rc::Rc};
+
+use crate::{car::Car, controls::Controls};
+
+/// Turns the controls of one tick into a new speed, heading and position.
+pub trait VehicleModel: Debug {
+    fn name(&self) -> &'static str;
+
+    fn step(&self, car: &mut Car, controls: &Controls);
+}
+
+/// The model a car drives with. Defaults to [`Arcade`].
+#[derive(Debug, Clone)]
+pub struct Vehicle(pub Rc<dyn VehicleModel>);
+
+impl Vehicle {
+    pub fn new(model: impl VehicleModel + 'static) -> Self {
+        Self(Rc::new(model))
+    }
+}
+
+impl Default for Vehicle {
+    fn default() -> Self {
+        Self::new(Arcade::default())
+    }
+}
+
+impl Deref for Vehicle {
+    type Target = dyn VehicleModel;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Throttle, brake, speed limits and friction, shared by every model.
+fn update_speed(car: &mut Car, controls: &Controls) {
+    car.speed += car.acceleration * controls.throttle.clamp(0.0, 1.0);
+    car.speed -= car.acceleration * controls.brake.clamp(0.0, 1.0);
+
+    if car.speed > car.max_speed {
+        car.speed = car.max_speed;
+    }
+
+    if car.speed < -car.max_speed / 2.0 {
+        car.speed = -car.max_speed / 2.0;
+    }
+
+    if car.speed > 0.0 {
+        car.speed -= car.friction;
+    }
+
+    if car.speed < 0.0 {
+        car.speed += car.friction;
+    }
+
+    if car.speed.abs() < car.friction {
+        car.speed = 0.0;
+    }
+}
+
+/// The tutorial's physics: the heading turns at a fixed rate whenever the
+/// car moves, however fast it goes.
+#[derive(Debug, Clone, Copy)]
+pub struct Arcade {
+    /// Turn per tick, in radians, at full steering.
+    pub steering_rate: f64,
+}
+
+impl Default for Arcade {
+    fn default() -> Self {
+        Self {
+            steering_rate: 0.03,
+        }
+    }
+}
+
+impl VehicleModel for Arcade {
+    fn name(&self) -> &'static str {
+        "arcade"
+    }
+
+    fn step(&self, car: &mut Car, controls: &Controls) {
+        update_speed(car, controls);
+
+        if car.speed != 0.0 {
+            let flip = if car.speed > 0.0 { 1.0 } else { -1.0 };
+
+            car.angle += self.steering_rate * controls.steering.clamp(-1.0, 1.0) * flip;
+        }
+
+        car.x -= car.angle.sin() * car.speed;
+        car.y -= car.angle.cos() * car.speed;
+    }
+}
+
+/// Kinematic bicycle model: steering sets the front wheel angle, and the
+/// heading turns by `speed / wheelbase * tan(angle)`. The wheel angle is
+/// further limited so the lateral acceleration stays bounded, which widens
+/// the turning radius as the car speeds up.
+#[derive(Debug, Clone, Copy)]
+pub struct KinematicBicycle {
+    /// Distance between the axles, in pixels.
+    pub wheelbase: f64,
+    /// Front wheel angle at full steering, in radians.
+    pub max_steering_angle: f64,
+    /// Highest `speed² / turning radius`, in pixels per tick².
+    pub max_lateral_acceleration: f64,
+}
+
+impl Default for KinematicBicycle {
+    fn default() -> Self {
+        Self {
+            wheelbase: 30.0,
+            max_steering_angle: 0.5,
+            max_lateral_acceleration: 0.1,
+        }
+    }
+}
+
+impl KinematicBicycle {
+    /// Smallest turning radius reachable at `speed`.
+    pub fn turning_radius(&self, speed: f64) -> f64 {
+        self.wheelbase / self.max_tan_steering(speed)
+    }
+
+    fn max_tan_steering(&self, speed: f64) -> f64 {
+        let grip_limit = self.max_lateral_acceleration * self.wheelbase / (speed * speed);
+        self.max_steering_angle.tan().min(grip_limit)
+    }
+}
+
+impl VehicleModel for KinematicBicycle {
+    fn name(&self) -> &'static str {
+        "bicycle"
+    }
+
+    fn step(&self, car: &mut Car, controls: &Controls) {
+        update_speed(car, controls);
+
+        let max_tan = self.max_tan_steering(car.speed);
+        let tan_steering = (controls.steering.clamp(-1.0, 1.0) * self.max_steering_angle)
+            .tan()
+            .clamp(-max_tan, max_tan);
+        car.angle += car.speed / self.wheelbase * tan_steering;
+
+        car.x -= car.angle.sin() * car.speed;
+        car.y -= car.angle.cos() * car.speed;
+    }
+}
+
+pub fn built_in() -> Vec<Vehicle> {
+    vec![
+        Vehicle::new(Arcade::default()),
+        Vehicle::new(KinematicBicycle::default()),
+    ]
+}
+
+pub fn from_name(name: &str) -> Option<Vehicle> {
+    built_in()
+        .into_iter()
+        .find(|vehicle| vehicle.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KinematicBicycle;
+
+    #[test]
+    fn test_turning_radius_grows_with_speed() {
+        let bicycle = KinematicBicycle::default();
+
+        let slow = bicycle.turning_radius(0.5);
+        let fast = bicycle.turning_radius(3.0);
+        assert_eq!(slow, bicycle.wheelbase / bicycle.max_steering_angle.tan());
+        assert!(fast > slow);
+    }
+}
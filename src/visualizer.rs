@@ -5,6 +5,7 @@ use wasm_bindgen::JsValue;
 use web_sys::CanvasRenderingContext2d;
 
 use crate::{
+    controls::ControlMode,
     network::{Level, NeuralNetwork},
     utils::{get_rgba, lerp},
 };
@@ -50,7 +51,10 @@ pub fn draw_network(ctx: &CanvasRenderingContext2d, network: &NeuralNetwork) {
             width,
             level_height,
             if i == network.levels.len() - 1 {
-                vec!["⬆️", "⬅️", "➡️", "⬇️"]
+                match network.control_mode {
+                    ControlMode::Buttons => vec!["⬆️", "⬅️", "➡️", "⬇️"],
+                    ControlMode::Analog => vec!["↕️", "↔️"],
+                }
             } else {
                 vec![]
             },
@@ -74,15 +78,16 @@ pub fn draw_level(
         outputs,
         biases,
         weights,
+        ..
     } = level;
 
     (0..inputs.len()).for_each(|i| {
-        for j in 0..outputs.len() {
+        for (j, weight) in weights[i].iter().enumerate() {
             ctx.begin_path();
             ctx.move_to(get_node_x(inputs, i as f64, left, right), bottom);
             ctx.line_to(get_node_x(outputs, j as f64, left, right), top);
             ctx.set_line_width(2.0);
-            ctx.set_stroke_style(&JsValue::from_str(&get_rgba(weights[i][j])));
+            ctx.set_stroke_style_str(&get_rgba(*weight));
             ctx.stroke();
         }
     });
@@ -93,12 +98,12 @@ pub fn draw_level(
         ctx.begin_path();
         ctx.arc(x, bottom, node_radius as f64, 0.0, PI * 2.0)
             .unwrap();
-        ctx.set_fill_style(&JsValue::from_str("black"));
+        ctx.set_fill_style_str("black");
         ctx.fill();
         ctx.begin_path();
         ctx.arc(x, bottom, node_radius as f64 * 0.6, 0.0, PI * 2.0)
             .unwrap();
-        ctx.set_fill_style(&JsValue::from_str(&get_rgba(inputs[i])));
+        ctx.set_fill_style_str(&get_rgba(inputs[i]));
         ctx.fill();
     }
 
@@ -106,19 +111,19 @@ pub fn draw_level(
         let x = get_node_x(outputs, i as f64, left, right);
         ctx.begin_path();
         ctx.arc(x, top, node_radius as f64, 0.0, PI * 2.0).unwrap();
-        ctx.set_fill_style(&JsValue::from_str("black"));
+        ctx.set_fill_style_str("black");
         ctx.fill();
         ctx.begin_path();
         ctx.arc(x, top, node_radius as f64 * 0.6, 0.0, PI * 2.0)
             .unwrap();
-        ctx.set_fill_style(&JsValue::from_str(&get_rgba(outputs[i])));
+        ctx.set_fill_style_str(&get_rgba(outputs[i]));
         ctx.fill();
 
         ctx.begin_path();
         ctx.set_line_width(2.0);
         ctx.arc(x, top, node_radius as f64 * 0.8, 0.0, PI * 2.0)
             .unwrap();
-        ctx.set_stroke_style(&JsValue::from_str(&get_rgba(biases[i])));
+        ctx.set_stroke_style_str(&get_rgba(biases[i]));
 
         let line_dash = Array::new();
         line_dash.push(&JsValue::from_f64(3.0));
@@ -132,8 +137,8 @@ pub fn draw_level(
             ctx.begin_path();
             ctx.set_text_align("center");
             ctx.set_text_baseline("middle");
-            ctx.set_fill_style(&JsValue::from_str("black"));
-            ctx.set_stroke_style(&JsValue::from_str("white"));
+            ctx.set_fill_style_str("black");
+            ctx.set_stroke_style_str("white");
             ctx.set_font(&format!("{:.2}px Arial", node_radius as f64 * 1.5));
             ctx.fill_text(output_label, x, top + node_radius as f64 * 0.1)
                 .unwrap();
@@ -0,0 +1,909 @@
+use std::{collections::VecDeque, rc::Rc, str::FromStr};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    agent::{steer_to_lane, Neighbor},
+    brain::{self, BrainError},
+    car::{Car, CarPtr},
+    controls::{ControlKind, Controls},
+    damage::DamageModel,
+    fitness::{self, DrivingStats, Fitness},
+    genetics::{self, GeneticConfig},
+    network::{BrainConfig, NeuralNetwork},
+    noise::{NoiseConfig, SensorNoise},
+    road::Road,
+    scenario::{Scenario, Spawn, TrafficEntry},
+    sensor::Sensor,
+    spatial::Obstacles,
+    traffic::TrafficSpawner,
+    utils::{get_random_color, Coord},
+    vehicle::Vehicle,
+};
+
+/// When a generation ends and how the next population is bred from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationConfig {
+    /// Ticks after which the generation ends regardless of the population.
+    pub tick_budget: usize,
+    /// A car that gets less than `min_progress` further along the road
+    /// over this many ticks is eliminated.
+    pub stuck_ticks: usize,
+    pub min_progress: f64,
+    /// A car this far along the road behind the best car is eliminated.
+    pub max_lag: Option<f64>,
+    /// How the next population is bred from this one.
+    pub genetics: GeneticConfig,
+}
+
+impl Default for GenerationConfig {
+    fn default() -> Self {
+        Self {
+            tick_budget: 3000,
+            stuck_ticks: 200,
+            min_progress: 10.0,
+            max_lag: None,
+            genetics: GeneticConfig::default(),
+        }
+    }
+}
+
+/// How the AI cars of a population share the road.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Interaction {
+    /// The cars drive through each other and share one traffic, which
+    /// reacts to all of them.
+    #[default]
+    Ghosts,
+    /// The cars also sense and hit each other, found through the same
+    /// spatial index as the traffic. They start lined up in rows behind the
+    /// starting line rather than on top of each other, so a large
+    /// population starts far back.
+    Collide,
+    /// The cars drive through each other, each in its own copy of the
+    /// traffic that only reacts to it. Costs one traffic simulation per car.
+    PrivateTraffic,
+}
+
+impl Interaction {
+    pub const ALL: [Self; 3] = [Self::Ghosts, Self::Collide, Self::PrivateTraffic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ghosts => "ghosts",
+            Self::Collide => "collide",
+            Self::PrivateTraffic => "private-traffic",
+        }
+    }
+}
+
+impl FromStr for Interaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|interaction| interaction.name() == s)
+            .ok_or_else(|| format!("unknown interaction `{s}`"))
+    }
+}
+
+/// Rows of AI cars lined up under [`Interaction::Collide`] are this far
+/// apart, enough to clear a car.
+const ROW_SPACING: f64 = 80.0;
+
+/// One AI car's copy of the traffic, under [`Interaction::PrivateTraffic`].
+#[derive(Debug, Clone)]
+struct PrivateTraffic {
+    traffic: Vec<CarPtr>,
+    spawner: Option<TrafficSpawner>,
+}
+
+impl PrivateTraffic {
+    /// A copy of freshly generated `traffic` with controls of its own, which
+    /// plain clones would share with the originals.
+    fn copy(traffic: &[CarPtr], spawner: Option<&TrafficSpawner>) -> Self {
+        let traffic = traffic
+            .iter()
+            .map(|car| {
+                let mut copy = car.clone();
+                let kind = match car.agent {
+                    Some(_) => ControlKind::Agent,
+                    None => ControlKind::Dummy,
+                };
+                copy.controls = Controls::new(kind);
+                copy
+            })
+            .collect();
+        Self {
+            traffic,
+            spawner: spawner.cloned(),
+        }
+    }
+}
+
+/// The headless simulation: a road, its traffic and the population of AI
+/// cars. Nothing in here touches `web_sys`, so it can be stepped natively.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub road: Road,
+    pub traffic: Vec<CarPtr>,
+    pub cars: Vec<CarPtr>,
+    pub tick: usize,
+    pub generation: usize,
+    pub config: GenerationConfig,
+    pub fitness: Rc<dyn Fitness>,
+    /// Layout of the brains in a population generated from scratch.
+    pub brain: BrainConfig,
+    /// Vehicle model given to every generated AI car.
+    pub vehicle: Vehicle,
+    /// Sensor noise given to every generated AI car.
+    pub noise: NoiseConfig,
+    /// How hard generated AI cars can hit something before they are damaged.
+    pub damage: DamageModel,
+    /// Whether AI cars meet each other, and whose traffic they drive in.
+    pub interaction: Interaction,
+    /// Traffic and starting point of every generation. The road is built from
+    /// it by whoever creates the world.
+    pub scenario: Scenario,
+    /// Endless traffic ahead of the population. Without one, every generation
+    /// gets the scenario's traffic. Under [`Interaction::PrivateTraffic`] the
+    /// traffic is handed out to the cars, leaving this one empty.
+    pub spawner: Option<TrafficSpawner>,
+    /// Every random decision in the simulation is drawn from `rng`, so two
+    /// worlds built from the same seed and config run bit-identically.
+    pub seed: u64,
+    pub rng: StdRng,
+    best_car: Option<usize>,
+    /// Stations of every AI car over the last `stuck_ticks` ticks.
+    recent_stations: Vec<VecDeque<f64>>,
+    private_traffic: Vec<PrivateTraffic>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new(Road::default(), Vec::new(), Vec::new(), 0)
+    }
+}
+
+impl World {
+    pub fn new(road: Road, traffic: Vec<CarPtr>, cars: Vec<CarPtr>, seed: u64) -> Self {
+        Self {
+            road,
+            traffic,
+            recent_stations: vec![VecDeque::new(); cars.len()],
+            cars,
+            tick: 0,
+            generation: 0,
+            config: GenerationConfig::default(),
+            fitness: Rc::new(fitness::Distance),
+            brain: BrainConfig::default(),
+            vehicle: Vehicle::default(),
+            noise: NoiseConfig::default(),
+            damage: DamageModel::default(),
+            interaction: Interaction::default(),
+            scenario: Scenario::default(),
+            spawner: None,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            best_car: None,
+            private_traffic: Vec::new(),
+        }
+    }
+
+    /// The default setup: the tutorial's traffic and `population` AI cars,
+    /// all drawn from `seed`.
+    pub fn generate(road: Road, population: usize, seed: u64) -> Self {
+        let mut world = Self::new(road, Vec::new(), Vec::new(), seed);
+        world.populate(population);
+        world
+    }
+
+    /// Replaces traffic and cars with freshly generated ones, using the
+    /// current [`BrainConfig`], and restarts the generation clock.
+    pub fn populate(&mut self, population: usize) {
+        self.road.reset();
+        self.reset_traffic();
+        let brains = (0..population)
+            .map(|_| self.brain.build(&mut self.rng))
+            .collect();
+        self.cars = Self::generate_cars(&self.road, self.scenario.spawn, brains, &self.vehicle);
+        self.equip_cars();
+        self.share_road();
+        self.spawn_traffic();
+        self.recent_stations = vec![VecDeque::new(); population];
+        self.best_car = None;
+        self.tick = 0;
+    }
+
+    /// A world at generation zero on the scenario's road, built from `seed`,
+    /// with the same settings and a fresh population from the scenario.
+    pub fn restarted(&self, seed: u64) -> Self {
+        let mut world = Self::new(self.scenario.road.build(seed), Vec::new(), Vec::new(), seed);
+        world.config = self.config;
+        world.fitness = self.fitness.clone();
+        world.brain = self.brain.clone();
+        world.vehicle = self.vehicle.clone();
+        world.noise = self.noise;
+        world.damage = self.damage;
+        world.interaction = self.interaction;
+        world.spawner = self
+            .spawner
+            .as_ref()
+            .map(|spawner| TrafficSpawner::new(spawner.config, seed));
+        // Populating draws the traffic and starting point from the scenario.
+        world.scenario = self.scenario.clone();
+        world.populate(world.scenario.population);
+        world
+    }
+
+    /// One AI car per brain, all at the starting line.
+    pub fn generate_cars(
+        road: &Road,
+        spawn: Spawn,
+        brains: Vec<NeuralNetwork>,
+        vehicle: &Vehicle,
+    ) -> Vec<CarPtr> {
+        let mut cars = Vec::new();
+        for (i, brain) in brains.into_iter().enumerate() {
+            let mut car = Car::new(
+                0.0,
+                0.0,
+                30.0,
+                50.0,
+                ControlKind::AI,
+                None,
+                None,
+                Some(brain),
+            );
+            Self::place(&mut car, road, spawn.lane, -spawn.y);
+            car.vehicle = vehicle.clone();
+            car.member = Some(i);
+            cars.push(car);
+        }
+
+        cars
+    }
+
+    pub fn generate_traffic(
+        road: &Road,
+        entries: &[TrafficEntry],
+        rng: &mut impl Rng,
+    ) -> Vec<CarPtr> {
+        entries
+            .iter()
+            .map(|entry| {
+                let color = match &entry.color {
+                    Some(color) => color.clone(),
+                    None => get_random_color(rng),
+                };
+                let mut car = Car::new(
+                    0.0,
+                    0.0,
+                    30.0,
+                    50.0,
+                    entry.control,
+                    Some(entry.speed),
+                    Some(&color),
+                    None,
+                );
+                Self::place(&mut car, road, entry.lane, -entry.y);
+                car
+            })
+            .collect()
+    }
+
+    /// Puts `car` on the center of `lane` at `station`, facing along the road.
+    pub fn place(car: &mut Car, road: &Road, lane: usize, station: f64) {
+        let center = road.lane_center(lane, station);
+        car.x = center.x;
+        car.y = center.y;
+        car.angle = road.point_at(station).1;
+        car.stats = DrivingStats::new(station);
+        car.stats.lateral = road.lane_offset(lane);
+        if let Some(agent) = &mut car.agent {
+            agent.lane = lane;
+        }
+    }
+
+    /// Steers a traffic car towards the nearest lane center and along the
+    /// road, so traffic can follow curves.
+    fn follow_lane(car: &Car, road: &Road) {
+        let (station, lateral) = road.project(Coord { x: car.x, y: car.y });
+        let lane = road.nearest_lane(lateral);
+
+        car.controls.borrow_mut().steering = steer_to_lane(car, road, station, lateral, lane);
+    }
+
+    /// Gives every car a copy of `brain`, and the sensor it was trained
+    /// with, mutating all but the first one by `amount` so the saved brain
+    /// itself always takes part. Fails, leaving the cars alone, if the brain
+    /// is malformed.
+    pub fn load_brain(&mut self, brain: &NeuralNetwork, amount: f64) -> Result<(), BrainError> {
+        brain::validate_network(brain)?;
+
+        for (i, car) in self.cars.iter_mut().enumerate() {
+            let mut brain = brain.clone();
+            if i != 0 {
+                brain.mutate(Some(amount), &mut self.rng);
+            }
+            car.sensor = Some(Sensor::new(brain.sensor));
+            car.brain = Some(brain);
+        }
+        Ok(())
+    }
+
+    pub fn step(&mut self) {
+        // Traffic only runs into the road borders.
+        let borders = Obstacles::new(&self.road.borders, &[]);
+
+        if self.interaction == Interaction::PrivateTraffic {
+            for i in 0..self.cars.len() {
+                if self.cars[i].eliminated {
+                    continue;
+                }
+                let traffic = &mut self.private_traffic[i].traffic;
+                let population = std::slice::from_ref(&self.cars[i]);
+                Self::step_traffic(traffic, population, &self.road, &borders);
+
+                // The borders are indexed once per tick, not once per car.
+                let mut obstacles = borders.clone();
+                obstacles.add_traffic(traffic);
+                self.cars[i].update(&obstacles);
+                self.record(i);
+            }
+        } else {
+            Self::step_traffic(&mut self.traffic, &self.cars, &self.road, &borders);
+
+            // Like agents, AI cars meet each other as they were at the end
+            // of the last tick. The traffic is done with the borders.
+            let mut obstacles = borders;
+            obstacles.add_traffic(&self.traffic);
+            if self.interaction == Interaction::Collide {
+                obstacles.add_population(&self.cars);
+            }
+            for i in 0..self.cars.len() {
+                if self.cars[i].eliminated {
+                    continue;
+                }
+                obstacles.member = Some(i);
+                self.cars[i].update(&obstacles);
+                self.record(i);
+            }
+        }
+
+        for i in 0..self.cars.len() {
+            self.cars[i].fitness = self.fitness.score(&self.cars[i], self);
+        }
+
+        self.best_car = self
+            .cars
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.fitness.total_cmp(&b.fitness))
+            .map(|(i, _)| i);
+        self.eliminate_laggards();
+
+        if self.road.is_procedural() {
+            self.follow_road();
+        }
+        self.spawn_traffic();
+
+        self.tick += 1;
+    }
+
+    /// Drives `traffic` for one tick, its agents reacting to each other and
+    /// to the cars of `population` still driving.
+    fn step_traffic(
+        traffic: &mut [CarPtr],
+        population: &[CarPtr],
+        road: &Road,
+        borders: &Obstacles,
+    ) {
+        // Agents see every car as it was at the end of the last tick.
+        let neighbors = traffic
+            .iter()
+            .chain(
+                population
+                    .iter()
+                    .filter(|car| !car.damaged && !car.eliminated),
+            )
+            .map(|car| Neighbor::of(car, road))
+            .collect::<Vec<Neighbor>>();
+
+        for (i, car) in traffic.iter_mut().enumerate() {
+            match car.agent {
+                Some(mut agent) => {
+                    let controls = agent.drive(car, road, &neighbors, i);
+                    *car.controls.borrow_mut() = controls;
+                    car.agent = Some(agent);
+                }
+                None => Self::follow_lane(car, road),
+            }
+            car.update(borders);
+
+            let mut stats = car.stats;
+            stats.record(car, road);
+            car.stats = stats;
+        }
+    }
+
+    /// Records the stats of the AI car at `index` after it moved, and
+    /// eliminates it if it has made too little progress lately.
+    fn record(&mut self, index: usize) {
+        let car = &mut self.cars[index];
+        let mut stats = car.stats;
+        stats.record(car, &self.road);
+        car.stats = stats;
+
+        let stations = &mut self.recent_stations[index];
+        stations.push_back(car.stats.station);
+        if stations.len() > self.config.stuck_ticks {
+            let progress = car.stats.station - stations.pop_front().unwrap_or_default();
+            if progress < self.config.min_progress && !car.damaged {
+                Self::eliminate(car);
+            }
+        }
+    }
+
+    /// Eliminates the cars that fell more than `max_lag` behind the best one.
+    fn eliminate_laggards(&mut self) {
+        let (Some(max_lag), Some(best)) = (self.config.max_lag, self.best_car) else {
+            return;
+        };
+
+        let leader = self.cars[best].stats.station;
+        for car in &mut self.cars {
+            if !car.damaged && !car.eliminated && leader - car.stats.station > max_lag {
+                Self::eliminate(car);
+            }
+        }
+    }
+
+    /// Takes `car` out of the generation. It stops where it is and keeps its
+    /// stats and fitness, but is no longer updated.
+    fn eliminate(car: &mut Car) {
+        car.eliminated = true;
+        car.speed = 0.0;
+    }
+
+    /// Sets up a fresh population for the [`Interaction`]: lines the cars up
+    /// when they collide, or hands each one a copy of the traffic.
+    fn share_road(&mut self) {
+        self.private_traffic = Vec::new();
+
+        match self.interaction {
+            Interaction::Ghosts => (),
+            Interaction::Collide => {
+                let spawn = self.scenario.spawn;
+                let lanes = self.road.lane_count;
+                for (i, car) in self.cars.iter_mut().enumerate() {
+                    let row = (i / lanes) as f64;
+                    let station = -spawn.y - row * ROW_SPACING;
+                    Self::place(car, &self.road, (spawn.lane + i) % lanes, station);
+                }
+            }
+            Interaction::PrivateTraffic => {
+                let traffic = std::mem::take(&mut self.traffic);
+                self.private_traffic = (0..self.cars.len())
+                    .map(|_| PrivateTraffic::copy(&traffic, self.spawner.as_ref()))
+                    .collect();
+            }
+        }
+    }
+
+    /// The traffic `car` drives in, and the spawner feeding it: its own copy
+    /// under [`Interaction::PrivateTraffic`], the shared one otherwise.
+    pub fn traffic_of(&self, car: &Car) -> (&[CarPtr], Option<&TrafficSpawner>) {
+        match car.member.and_then(|i| self.private_traffic.get(i)) {
+            Some(copy) => (&copy.traffic, copy.spawner.as_ref()),
+            None => (&self.traffic, self.spawner.as_ref()),
+        }
+    }
+
+    /// Gives every AI car the damage model and its own [`SensorNoise`].
+    /// Clean sensors draw nothing from `rng`, so they leave the rest of the
+    /// run unchanged.
+    fn equip_cars(&mut self) {
+        for car in &mut self.cars {
+            car.damage = self.damage;
+            if !self.noise.is_clean() {
+                car.noise = Some(SensorNoise::new(self.noise, self.rng.gen()));
+            }
+        }
+    }
+
+    /// Clears the traffic of the last generation: restarts the spawner, or
+    /// brings back the fixed traffic when there is none.
+    fn reset_traffic(&mut self) {
+        match &mut self.spawner {
+            Some(spawner) => {
+                spawner.reset();
+                self.traffic = Vec::new();
+            }
+            None => {
+                self.traffic =
+                    Self::generate_traffic(&self.road, &self.scenario.traffic, &mut self.rng)
+            }
+        }
+    }
+
+    /// Lets the spawner add traffic ahead of the leading AI car and drop the
+    /// traffic behind the last one. Private copies of the traffic follow
+    /// their own car.
+    fn spawn_traffic(&mut self) {
+        if self.interaction == Interaction::PrivateTraffic {
+            for (car, copy) in self.cars.iter().zip(&mut self.private_traffic) {
+                if let Some(spawner) = &mut copy.spawner {
+                    let station = car.stats.station;
+                    spawner.update(&self.road, &mut copy.traffic, station, station);
+                }
+            }
+            return;
+        }
+
+        let Some(spawner) = &mut self.spawner else {
+            return;
+        };
+
+        // Wrecks and eliminated cars are left behind, unless no car is left.
+        let running = |car: &&CarPtr| !car.damaged && !car.eliminated;
+        let any_running = self.cars.iter().any(|car| running(&car));
+        let stations = self
+            .cars
+            .iter()
+            .filter(|car| !any_running || running(car))
+            .map(|car| car.stats.station);
+        let leader = stations.clone().fold(f64::NEG_INFINITY, f64::max);
+        let trailer = stations.fold(f64::INFINITY, f64::min);
+        if leader.is_finite() {
+            spawner.update(&self.road, &mut self.traffic, leader, trailer);
+        }
+    }
+
+    /// Keeps a procedural road generated ahead of the leading car and drops
+    /// what every AI car still driving has left behind.
+    fn follow_road(&mut self) {
+        let stations = || {
+            self.cars
+                .iter()
+                .chain(&self.traffic)
+                .chain(self.private_traffic.iter().flat_map(|copy| &copy.traffic))
+                .map(|car| car.stats.station)
+        };
+        let ahead = stations().fold(f64::NEG_INFINITY, f64::max);
+        self.road.extend_to(ahead + 2_000.0);
+
+        let behind = self
+            .cars
+            .iter()
+            .filter(|car| !car.damaged && !car.eliminated)
+            .map(|car| car.stats.station)
+            .fold(f64::INFINITY, f64::min);
+        if behind.is_finite() {
+            self.road.trim_before(behind - 1_000.0);
+        }
+    }
+
+    pub fn best_car(&self) -> Option<&CarPtr> {
+        self.best_car.map(|i| &self.cars[i])
+    }
+
+    /// A generation is over once the tick budget is spent or every car is
+    /// damaged or eliminated.
+    pub fn generation_over(&self) -> bool {
+        self.tick >= self.config.tick_budget
+            || self.cars.iter().all(|car| car.damaged || car.eliminated)
+    }
+
+    /// Resets traffic and population, breeding the new cars from the scored
+    /// brains of this generation. Returns the best brain so the caller can
+    /// persist it.
+    pub fn next_generation(&mut self) -> Option<NeuralNetwork> {
+        let best_brain = self.best_car().and_then(|car| car.brain.clone());
+        let (brains, fitness): (Vec<NeuralNetwork>, Vec<f64>) = self
+            .cars
+            .iter()
+            .filter_map(|car| Some((car.brain.clone()?, car.fitness)))
+            .unzip();
+
+        let next = genetics::breed(
+            &brains,
+            &fitness,
+            self.generation,
+            &self.config.genetics,
+            &mut self.rng,
+        );
+        self.road.reset();
+        self.reset_traffic();
+        self.cars = Self::generate_cars(&self.road, self.scenario.spawn, next, &self.vehicle);
+        self.equip_cars();
+        self.share_road();
+        self.spawn_traffic();
+
+        self.recent_stations = vec![VecDeque::new(); self.cars.len()];
+        self.best_car = None;
+        self.tick = 0;
+        self.generation += 1;
+
+        best_brain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{GenerationConfig, Interaction, World};
+    use crate::{
+        controls::ControlKind,
+        road::Road,
+        scenario::Scenario,
+        traffic::{TrafficConfig, TrafficSpawner},
+    };
+
+    fn world(amount: usize) -> World {
+        World::generate(Road::new(100.0, 180.0, None), amount, 7)
+    }
+
+    #[test]
+    fn test_step_headless() {
+        let mut world = world(20);
+        for _ in 0..5_000 {
+            world.step();
+        }
+
+        assert_eq!(world.tick, 5_000);
+        assert!(world.best_car().is_some());
+        assert!(world.traffic.iter().all(|t| !t.damaged));
+        assert!(world.traffic.iter().all(|t| t.y < -100.0));
+    }
+
+    #[test]
+    fn test_traffic_follows_curvy_road() {
+        let mut world = World::generate(Road::procedural(100.0, 180.0, None, 3), 5, 7);
+        for _ in 0..3_000 {
+            world.step();
+        }
+
+        assert!(world
+            .road
+            .headings
+            .iter()
+            .any(|heading| heading.abs() > 0.1));
+        assert!(world.traffic.iter().all(|t| !t.damaged));
+        assert!(world.traffic.iter().all(|t| t.stats.station > 3_000.0));
+    }
+
+    #[test]
+    fn test_agents_change_lanes_without_crashing() {
+        let mut world = world(5);
+        world.spawner = Some(TrafficSpawner::new(
+            TrafficConfig {
+                speed: "0.5:2.5".parse().unwrap(),
+                driver: ControlKind::Agent,
+                ..Default::default()
+            },
+            7,
+        ));
+        world.populate(5);
+
+        let lanes = world
+            .traffic
+            .iter()
+            .map(|t| t.agent.unwrap().lane)
+            .collect::<Vec<usize>>();
+        for _ in 0..2_000 {
+            world.step();
+        }
+
+        assert!(world.traffic.iter().all(|t| !t.damaged && t.speed >= 0.0));
+        assert!(world
+            .traffic
+            .iter()
+            .zip(&lanes)
+            .any(|(t, &lane)| t.agent.unwrap().lane != lane));
+    }
+
+    #[test]
+    fn test_colliding_cars_hit_each_other() {
+        let mut world = world(0);
+        world.interaction = Interaction::Collide;
+        world.populate(9);
+        world.step();
+        assert!(world.cars.iter().all(|car| !car.damaged));
+        // Three rows of three, the second one right behind the first.
+        assert_eq!(world.cars[3].stats.lateral, world.cars[0].stats.lateral);
+        assert!((world.cars[0].y - world.cars[3].y + 80.0).abs() < 1.0);
+
+        let (x, y) = (world.cars[0].x, world.cars[0].y);
+        world.cars[3].x = x;
+        world.cars[3].y = y + 20.0;
+        world.step();
+        world.step();
+        assert!(world.cars[0].damaged && world.cars[3].damaged);
+        assert_eq!(world.cars.iter().filter(|car| car.damaged).count(), 2);
+    }
+
+    #[test]
+    fn test_eliminated_cars_do_not_hold_back_traffic() {
+        let mut world = world(0);
+        world.spawner = Some(TrafficSpawner::new(TrafficConfig::default(), 7));
+        world.populate(2);
+        World::eliminate(&mut world.cars[1]);
+        world.cars[0].stats.station = 5_000.0;
+        world.spawn_traffic();
+
+        assert!(!world.spawner.as_ref().unwrap().removed.is_empty());
+    }
+
+    #[test]
+    fn test_eliminated_cars_are_not_obstacles() {
+        let mut world = world(0);
+        world.interaction = Interaction::Collide;
+        world.populate(9);
+        World::eliminate(&mut world.cars[0]);
+
+        // Car 3 drives right through where car 0 stopped.
+        let (x, y) = (world.cars[0].x, world.cars[0].y);
+        world.cars[3].x = x;
+        world.cars[3].y = y + 20.0;
+        world.step();
+        world.step();
+        assert!(world.cars.iter().all(|car| !car.damaged));
+    }
+
+    #[test]
+    fn test_restart_uses_the_loaded_scenario() {
+        let mut world = world(5);
+        world.scenario = Scenario::parse(
+            "(road: (lanes: 2), spawn: (lane: 0, y: 300.0), \
+             traffic: [(lane: 1, y: -400.0, color: Some(\"red\"))], population: 4)",
+        )
+        .unwrap();
+        let world = world.restarted(7);
+
+        let start = world.road.lane_center(0, -300.0);
+        assert_eq!(world.road.lane_count, 2);
+        assert_eq!(world.cars.len(), 4);
+        assert!(world
+            .cars
+            .iter()
+            .all(|car| car.x == start.x && car.y == start.y));
+        assert_eq!(world.traffic.len(), 1);
+        assert_eq!(world.traffic[0].color, "red");
+        assert_eq!(world.traffic[0].y, world.road.lane_center(1, 400.0).y);
+    }
+
+    #[test]
+    fn test_private_traffic_is_copied_per_car() {
+        let mut world = world(0);
+        world.interaction = Interaction::PrivateTraffic;
+        world.spawner = Some(TrafficSpawner::new(
+            TrafficConfig {
+                driver: ControlKind::Agent,
+                ..Default::default()
+            },
+            7,
+        ));
+        world.populate(3);
+        for _ in 0..500 {
+            world.step();
+        }
+
+        assert!(world.traffic.is_empty());
+        let traffic = world
+            .cars
+            .iter()
+            .map(|car| world.traffic_of(car).0)
+            .collect::<Vec<_>>();
+        assert!(traffic.iter().all(|traffic| !traffic.is_empty()));
+        assert!(!std::ptr::eq(traffic[0], traffic[1]));
+        assert!(!Rc::ptr_eq(
+            &traffic[0][0].controls,
+            &traffic[1][0].controls
+        ));
+        // Found by the car's place in the population, so a clone works too.
+        let copy = world.cars[1].clone();
+        assert!(std::ptr::eq(world.traffic_of(&copy).0, traffic[1]));
+        assert!(traffic.iter().flat_map(|t| t.iter()).all(|t| !t.damaged));
+    }
+
+    #[test]
+    fn test_cars_without_progress_are_eliminated() {
+        let mut world = world(0);
+        world.config.stuck_ticks = 50;
+        world.config.max_lag = Some(50.0);
+        world.populate(4);
+        // One car stands still, one reverses, one drives and one crawls.
+        for (car, throttle, brake) in [(0, 0.0, 0.0), (1, 0.0, 1.0), (2, 1.0, 0.0), (3, 0.5, 0.0)] {
+            world.cars[car].use_brain = false;
+            let mut controls = world.cars[car].controls.borrow_mut();
+            controls.throttle = throttle;
+            controls.brake = brake;
+        }
+
+        for _ in 0..60 {
+            world.step();
+        }
+        let eliminated = world
+            .cars
+            .iter()
+            .map(|car| car.eliminated)
+            .collect::<Vec<bool>>();
+        assert_eq!(eliminated, vec![true, true, false, true]);
+        assert!(world.cars.iter().all(|car| !car.damaged));
+        assert!(!world.generation_over());
+
+        // Eliminated cars stay where they are.
+        let y = world.cars[1].y;
+        world.step();
+        assert_eq!(world.cars[1].y, y);
+    }
+
+    #[test]
+    fn test_eliminated_cars_do_not_hold_back_the_road() {
+        let mut world = World::generate(Road::procedural(100.0, 180.0, None, 3), 2, 7);
+        world.traffic.clear();
+        World::eliminate(&mut world.cars[0]);
+        world.cars[1].stats.station = 5_000.0;
+        world.follow_road();
+
+        assert!(world.road.stations[0] > 3_000.0);
+    }
+
+    #[test]
+    fn test_generation_restarts_after_budget() {
+        let mut world = world(10);
+        world.config = GenerationConfig {
+            tick_budget: 100,
+            ..Default::default()
+        };
+
+        while !world.generation_over() {
+            world.step();
+        }
+        assert_eq!(world.tick, 100);
+
+        let best_brain = world.next_generation().unwrap();
+        assert_eq!(world.generation, 1);
+        assert_eq!(world.tick, 0);
+        assert_eq!(world.cars.len(), 10);
+        assert!(world.traffic.iter().all(|t| t.y >= -700.0));
+        // The default genetics keep the best brain as the single elite.
+        assert_eq!(
+            world.cars[0].brain.as_ref().unwrap().levels[0].weights,
+            best_brain.levels[0].weights
+        );
+    }
+
+    #[test]
+    fn test_same_seed_is_bit_identical() {
+        let run = || {
+            let mut world = world(10);
+            world.config.tick_budget = 300;
+            for _ in 0..2 {
+                while !world.generation_over() {
+                    world.step();
+                }
+                world.next_generation();
+            }
+            world
+        };
+
+        let (a, b) = (run(), run());
+        for (a, b) in a
+            .cars
+            .iter()
+            .zip(&b.cars)
+            .chain(a.traffic.iter().zip(&b.traffic))
+        {
+            assert_eq!(a.color, b.color);
+            let a_brain = a.brain.iter().flat_map(|brain| brain.parameters());
+            let b_brain = b.brain.iter().flat_map(|brain| brain.parameters());
+            assert!(a_brain
+                .map(|p| p.to_bits())
+                .eq(b_brain.map(|p| p.to_bits())));
+        }
+    }
+}